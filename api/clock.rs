@@ -1,7 +1,16 @@
-use core::fmt::{Display, Formatter};
+use core::fmt::{self, Display, Formatter};
+use core::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use core::str::FromStr;
 
-use crate::devices::cmos::CMOS;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::devices::cmos::{CMOS, RTC, RtcError};
+use crate::devices::nvram::{self, NvramError};
+use super::timezone::{DstRule, TimeZone};
+use super::wallclock;
 /// Holds a date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Date {
     pub year: u16,
     pub month: u8,
@@ -9,6 +18,7 @@ pub struct Date {
 }
 
 /// Holds a Time, accurate to the second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Time {
     pub hour: u8,
     pub minute: u8,
@@ -16,75 +26,366 @@ pub struct Time {
 }
 
 ///Wraps Together a Date & Time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DateTime {
-    date : Date,
-    time : Time
+    pub date : Date,
+    pub time : Time
 }
 
-/// Get the current time from the Real-Time Clock
-pub fn time() -> Time {
-    let rtc = CMOS::new().rtc();
-    Time {
-        second  : rtc.second,
-        minute  : rtc.minute,
-        hour    : rtc.hour,
+/// A Day of the Week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// Errors returned when parsing a Date or Time from a string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The input ended before a complete value was read
+    TooShort,
+    /// A character didn't match the expected format
+    InvalidCharacter(usize),
+    /// A field was outside of its valid range
+    OutOfRange,
+    /// Extra characters followed a complete value
+    TrailingCharacters,
+}
+
+/// How the Real-Time Clock's Date & Time should be interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcMode {
+    /// The RTC holds UTC
+    Utc,
+    /// The RTC holds local time in the configured Time Zone, as Windows does by default
+    Local,
+}
+
+static TIME_ZONE : Mutex<TimeZone> = Mutex::new(TimeZone::UTC);
+static RTC_MODE  : Mutex<RtcMode>  = Mutex::new(RtcMode::Utc);
+
+/// Sets the Time Zone used by `local_time`, and by the RTC in `RtcMode::Local`
+pub fn set_time_zone(zone : TimeZone) {
+    without_interrupts(|| *TIME_ZONE.lock() = zone);
+}
+
+pub fn time_zone() -> TimeZone {
+    without_interrupts(|| *TIME_ZONE.lock())
+}
+
+/// Sets whether the Real-Time Clock holds UTC or local time
+pub fn set_rtc_mode(mode : RtcMode) {
+    without_interrupts(|| *RTC_MODE.lock() = mode);
+}
+
+pub fn rtc_mode() -> RtcMode {
+    without_interrupts(|| *RTC_MODE.lock())
+}
+
+/// Starts the software clock from the Real-Time Clock.
+///
+/// The clock starts in the middle of the RTC's current second without waiting for it to tick over,
+/// then `maintain` aligns it to the next second edge it sees.
+/// Must be called once interrupts are enabled, after which time queries no longer touch the RTC.
+///
+/// `maintain` must be called regularly from outside interrupt handlers for the clock to follow
+/// the RTC, `pause` & `Executor::run` do. Without it the clock only keeps the PIT's accuracy.
+pub fn init() -> Result<(), RtcError> {
+    load_settings();
+    let current = read_rtc_utc()?;
+    wallclock::step_to(current.unix_time().as_secs() * 1_000_000_000 + 500_000_000);
+    wallclock::request_resync();
+    Ok(())
+}
+
+/// Stores the Time Zone & RTC mode in NVRAM, so they survive a reboot
+pub fn save_settings() -> Result<(), NvramError> {
+    let zone = time_zone();
+    let dst = match zone.dst {
+        DstRule::None => 0,
+        DstRule::Eu   => 1,
+        DstRule::Us   => 2,
+    };
+    let mode = match rtc_mode() {
+        RtcMode::Utc   => 0,
+        RtcMode::Local => 1,
+    };
+    nvram::set_i16(nvram::KEY_TIME_ZONE_OFFSET, (zone.offset / 60) as i16)?;
+    nvram::set_u8(nvram::KEY_DST_RULE, dst)?;
+    nvram::set_u8(nvram::KEY_RTC_MODE, mode)
+}
+
+/// Restores the Time Zone & RTC mode stored by `save_settings`, `init` calls this
+pub fn load_settings() {
+    if let Some(offset) = nvram::get_i16(nvram::KEY_TIME_ZONE_OFFSET) {
+        let dst = match nvram::get_u8(nvram::KEY_DST_RULE) {
+            Some(1) => DstRule::Eu,
+            Some(2) => DstRule::Us,
+            _       => DstRule::None,
+        };
+        set_time_zone(TimeZone::with_dst(offset as i32, dst));
+    }
+    match nvram::get_u8(nvram::KEY_RTC_MODE) {
+        Some(0) => set_rtc_mode(RtcMode::Utc),
+        Some(1) => set_rtc_mode(RtcMode::Local),
+        _ => {}
     }
 }
 
-/// Get the current Date from the Real-Time Clock
-pub fn date() -> Date {
-    let rtc = CMOS::new().rtc();
-    Date {
-        day     : rtc.day,
-        month   : rtc.month,
-        year    : rtc.year,
+/// Advances the software clock, called from the timer interrupt
+pub(crate) fn tick() {
+    wallclock::tick();
+}
+
+/// Compares the software clock against the Real-Time Clock, pulling it into the second the RTC
+/// reports. `maintain` then corrects any remaining drift once it sees the RTC's seconds tick over.
+pub fn sync() -> Result<(), RtcError> {
+    let rtc = read_rtc_utc()?;
+    wallclock::request_resync();
+    wallclock::resync(rtc.unix_time().as_secs());
+    Ok(())
+}
+
+/// While a resync is due, reads the Real-Time Clock until its seconds tick over & aligns the
+/// clock to that edge. Cheap to call often, the more often the closer the alignment.
+/// The idle loop calls it so the Real-Time Clock is never read from interrupt context.
+pub fn maintain() {
+    if wallclock::resync_pending() {
+        if let Ok(rtc) = read_rtc_utc() {
+            wallclock::resync(rtc.unix_time().as_secs());
+        }
     }
 }
 
-/// Get the current Date & Time from the Real-Time Clock
+/// Get the current local time
+pub fn time() -> Time {
+    local_time().time
+}
+
+/// Get the current local Date
+pub fn date() -> Date {
+    local_time().date
+}
+
+/// Get the current local Date & Time
 pub fn date_time() -> DateTime {
-    DateTime {date : date(), time : time()}
+    local_time()
 }
 
+/// Get the current Date & Time in the configured Time Zone,
+/// or the Unix Epoch if the Real-Time Clock couldn't be read
+pub fn local_time() -> DateTime {
+    try_local_time().unwrap_or(EPOCH)
+}
+
+/// Get the current Date & Time in UTC,
+/// or the Unix Epoch if the Real-Time Clock couldn't be read
+pub fn utc_time() -> DateTime {
+    try_utc_time().unwrap_or(EPOCH)
+}
+
+/// Get the current Date & Time in the configured Time Zone
+pub fn try_local_time() -> Result<DateTime, RtcError> {
+    Ok(time_zone().to_local(&try_utc_time()?))
+}
+
+/// Get the current Date & Time in UTC.
+///
+/// Reads the software clock once `init` has been called, the Real-Time Clock before that.
+pub fn try_utc_time() -> Result<DateTime, RtcError> {
+    if wallclock::is_synced() {
+        Ok(UnixTime(wallclock::now_ns().div_euclid(1_000_000_000)).to_date_time())
+    } else {
+        read_rtc_utc()
+    }
+}
+
+/// Errors returned when setting the clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockError {
+    Parse(ParseError),
+    Rtc(RtcError),
+}
+
+/// Sets the Real-Time Clock from a local Date & Time in the configured Time Zone
+pub fn set_date_time(local : DateTime) -> Result<(), ClockError> {
+    set_utc_time(time_zone().to_utc(&local))
+}
+
+/// Sets the Real-Time Clock from a Date & Time in UTC
+pub fn set_utc_time(utc : DateTime) -> Result<(), ClockError> {
+    if !utc.is_valid() {
+        return Err(ClockError::Rtc(RtcError::InvalidValue));
+    }
+    let dt = match rtc_mode() {
+        RtcMode::Utc   => utc,
+        RtcMode::Local => time_zone().to_local(&utc),
+    };
+    CMOS::new().set_rtc(RTC {
+        year   : dt.date.year,
+        month  : dt.date.month,
+        day    : dt.date.day,
+        hour   : dt.time.hour,
+        minute : dt.time.minute,
+        second : dt.time.second,
+    }).map_err(ClockError::Rtc)?;
+
+    if wallclock::is_synced() {
+        wallclock::step_to(utc.unix_time().as_secs() * 1_000_000_000);
+    }
+    Ok(())
+}
+
+/// Moves the clock forwards (or backwards) by a number of seconds
+pub fn adjust(seconds : i64) -> Result<(), ClockError> {
+    let now = try_utc_time().map_err(ClockError::Rtc)?;
+    set_utc_time(now + Duration::seconds(seconds))
+}
+
+/// Sets the clock from a string, for use by shells.
+///
+/// Accepts a full local Date & Time (`2021-03-14 15:09:26`, see `DateTime::parse_iso8601`),
+/// a local time of today (`15:09` or `15:09:26`), or a relative adjustment in seconds (`+30`, `-3600`).
+pub fn set_from_str(s : &str) -> Result<(), ClockError> {
+    let s = s.trim();
+    if s.starts_with('+') || s.starts_with('-') {
+        let seconds = s[1..].parse::<i64>().map_err(|_| ClockError::Parse(ParseError::InvalidCharacter(1)))?;
+        return adjust(if s.starts_with('-') { -seconds } else { seconds });
+    }
+
+    if s.as_bytes().get(2) == Some(&b':') {
+        let time = Time::parse(s).map_err(ClockError::Parse)?;
+        let today = try_local_time().map_err(ClockError::Rtc)?.date;
+        return set_date_time(DateTime::new(today, time));
+    }
+
+    set_date_time(DateTime::parse_iso8601(s).map_err(ClockError::Parse)?)
+}
+
+/// Reads the Real-Time Clock, converting it to UTC
+fn read_rtc_utc() -> Result<DateTime, RtcError> {
+    let rtc = CMOS::new().rtc()?;
+    let dt = DateTime {
+        date : Date { day : rtc.day, month : rtc.month, year : rtc.year },
+        time : Time { hour : rtc.hour, minute : rtc.minute, second : rtc.second },
+    };
+    Ok(match rtc_mode() {
+        RtcMode::Utc   => dt,
+        RtcMode::Local => time_zone().to_utc(&dt),
+    })
+}
+
+
+/// 1970-01-01 00:00:00
+pub const EPOCH : DateTime = DateTime {
+    date : Date { year : 1970, month : 1, day : 1 },
+    time : Time { hour : 0, minute : 0, second : 0 },
+};
 
 const DAYS_BEFORE_MONTH: [u64; 13] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334, 365];
 
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December"
+];
+
 /// Get the uptime of the kernal in Seconds
 pub fn uptime() -> f64 {
     (crate::interrupts::global_timer::current_tick() as f64) / crate::get_frequency() as f64
 }
 
-// NOTE: This clock is not monotonic
+/// Get the current time as seconds since 1970-01-01 00:00:00 UTC
+pub fn now() -> UnixTime {
+    utc_time().unix_time()
+}
+
+/// Get the current time as fractional seconds since 1970-01-01 00:00:00 UTC.
+// NOTE: Drift is slewed out gradually, but setting the clock or a large drift still steps it
 pub fn realtime() -> f64 {
-    let rtc = CMOS::new().rtc(); // Assuming GMT
+    if wallclock::is_synced() {
+        let ns = wallclock::now_ns();
+        let fract = ns.rem_euclid(1_000_000_000) as f64 / 1_000_000_000f64;
+        (ns.div_euclid(1_000_000_000) as f64) + fract
+    } else {
+        now().as_secs() as f64
+    }
+}
 
-    let timestamp = 86400 * days_before_year(rtc.year as u64)
-                  + 86400 * days_before_month(rtc.year as u64, rtc.month as u64)
-                  + 86400 * (rtc.day - 1) as u64
-                  +  3600 * rtc.hour as u64
-                  +    60 * rtc.minute as u64
-                  +         rtc.second as u64;
+/// Seconds since 1970-01-01 00:00:00 for the given Date & Time
+pub(crate) fn timestamp(dt : &DateTime) -> i64 {
+    let days = days_from_civil(dt.date.year as i64, dt.date.month as i64, dt.date.day as i64);
+    days * 86400
+        + 3600 * dt.time.hour as i64
+        +   60 * dt.time.minute as i64
+        +        dt.time.second as i64
+}
 
-    let fract = 0f64;
+/// Timestamps of 0000-01-01 00:00:00 & 65535-12-31 23:59:59, the range a `Date` can hold
+const MIN_TIMESTAMP : i64 = -62_167_219_200;
+const MAX_TIMESTAMP : i64 = 2_005_949_145_599;
 
-    (timestamp as f64) + fract
+/// The Date & Time of a number of seconds since 1970-01-01 00:00:00,
+/// clamped to the years 0 - 65535
+pub(crate) fn from_timestamp(timestamp : i64) -> DateTime {
+    let timestamp = timestamp.max(MIN_TIMESTAMP).min(MAX_TIMESTAMP);
+    let days = timestamp.div_euclid(86400);
+    let secs = timestamp.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    DateTime {
+        date : Date { year : year as u16, month : month as u8, day : day as u8 },
+        time : Time {
+            hour   : (secs / 3600) as u8,
+            minute : ((secs % 3600) / 60) as u8,
+            second : (secs % 60) as u8,
+        }
+    }
 }
 
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year : i64, month : i64, day : i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
 
+/// Proleptic Gregorian (year, month, day) for a number of days since 1970-01-01
+fn civil_from_days(days : i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
 
-
-fn days_before_year(year: u64) -> u64 {
-    (1970..year).fold(0, |days, y| {
-        days + if is_leap_year(y) { 366 } else { 365 }
-    })
+/// Returns the number of days in the month, accounting for leap years. 0 if the month isn't 1 - 12
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    if month < 1 || month > 12 {
+        return 0;
+    }
+    (days_before_month(year as u64, month as u64 + 1) - days_before_month(year as u64, month as u64)) as u8
 }
 
-fn days_before_month(year: u64, month: u64) -> u64 {
-    let leap_day = is_leap_year(year) && month > 2;
-    DAYS_BEFORE_MONTH[(month as usize) - 1] + if leap_day { 1 } else { 0 }
+/// Returns the number of days in the year, 366 for leap years
+pub fn days_in_year(year: u16) -> u16 {
+    if is_leap_year(year) { 366 } else { 365 }
 }
 
-fn is_leap_year(year: u64) -> bool {
+/// Returns true if the year has a February 29th
+pub fn is_leap_year(year: u16) -> bool {
     if year % 4 != 0 {
         false
     } else if year % 100 != 0 {
@@ -96,6 +397,562 @@ fn is_leap_year(year: u64) -> bool {
     }
 }
 
+/// Days in the year before the 1st of the month, month 13 gives the whole year
+fn days_before_month(year: u64, month: u64) -> u64 {
+    if month < 1 || month > 13 {
+        return 0;
+    }
+    let leap_day = is_leap_year(year as u16) && month > 2;
+    DAYS_BEFORE_MONTH[(month as usize) - 1] + if leap_day { 1 } else { 0 }
+}
+
+impl Weekday {
+    /// Monday = 0 ... Sunday = 6
+    pub fn from_monday(n : u8) -> Weekday {
+        match n % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+
+    /// Monday = 1 ... Sunday = 7, as used by ISO 8601
+    pub fn number_from_monday(self) -> u8 {
+        self as u8 + 1
+    }
+
+    /// Sunday = 0 ... Saturday = 6
+    pub fn number_from_sunday(self) -> u8 {
+        (self as u8 + 1) % 7
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weekday::Monday    => "Monday",
+            Weekday::Tuesday   => "Tuesday",
+            Weekday::Wednesday => "Wednesday",
+            Weekday::Thursday  => "Thursday",
+            Weekday::Friday    => "Friday",
+            Weekday::Saturday  => "Saturday",
+            Weekday::Sunday    => "Sunday",
+        }
+    }
+
+    pub fn short_name(self) -> &'static str {
+        &self.name()[..3]
+    }
+}
+
+impl Date {
+    pub fn new(year : u16, month : u8, day : u8) -> Date {
+        Date { year, month, day }
+    }
+
+    pub fn year(&self) -> u16 { self.year }
+    pub fn month(&self) -> u8 { self.month }
+    pub fn day(&self) -> u8 { self.day }
+
+    /// Returns true if the month & day exist in the year
+    pub fn is_valid(&self) -> bool {
+        self.month >= 1 && self.month <= 12 &&
+        self.day >= 1 && self.day <= days_in_month(self.year, self.month)
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        let days = days_from_civil(self.year as i64, self.month as i64, self.day as i64);
+        // 1970-01-01 was a Thursday
+        Weekday::from_monday((days + 3).rem_euclid(7) as u8)
+    }
+
+    /// Day of the Year, starting at 1 for January 1st. 0 if the month isn't 1 - 12
+    pub fn ordinal(&self) -> u16 {
+        if self.month < 1 || self.month > 12 {
+            return 0;
+        }
+        (days_before_month(self.year as u64, self.month as u64) + self.day as u64) as u16
+    }
+
+    /// Same as `ordinal`
+    pub fn day_of_year(&self) -> u16 {
+        self.ordinal()
+    }
+
+    /// Days in the Date's month, 0 if the month isn't 1 - 12
+    pub fn days_in_month(&self) -> u8 {
+        days_in_month(self.year, self.month)
+    }
+
+    pub fn is_leap_year(&self) -> bool {
+        is_leap_year(self.year)
+    }
+
+    /// Days since 1970-01-01, negative for earlier dates
+    pub fn days_since_epoch(&self) -> i64 {
+        days_from_civil(self.year as i64, self.month as i64, self.day as i64)
+    }
+
+    /// The Date a number of days since 1970-01-01
+    pub fn from_days_since_epoch(days : i64) -> Date {
+        let (year, month, day) = civil_from_days(days);
+        Date::new(year as u16, month as u8, day as u8)
+    }
+
+    pub fn month_name(&self) -> &'static str {
+        MONTH_NAMES[(self.month.max(1).min(12) - 1) as usize]
+    }
+
+    /// Formats the Date using `strftime`-style specifiers, see `DateTime::format`
+    pub fn format<'a>(&self, fmt : &'a str) -> Formatted<'a> {
+        DateTime::new(*self, Time::new(0, 0, 0)).format(fmt)
+    }
+}
+
+impl Time {
+    pub fn new(hour : u8, minute : u8, second : u8) -> Time {
+        Time { hour, minute, second }
+    }
+
+    pub fn hour(&self) -> u8 { self.hour }
+    pub fn minute(&self) -> u8 { self.minute }
+    pub fn second(&self) -> u8 { self.second }
+
+    pub fn is_valid(&self) -> bool {
+        self.hour < 24 && self.minute < 60 && self.second < 60
+    }
+
+    /// The hour on a 12-hour clock, 1 - 12
+    pub fn hour12(&self) -> u8 {
+        match self.hour % 12 {
+            0 => 12,
+            h => h
+        }
+    }
+
+    pub fn is_pm(&self) -> bool {
+        self.hour >= 12
+    }
+
+    /// Parses a time as `HH:MM[:SS]`
+    pub fn parse(s : &str) -> Result<Time, ParseError> {
+        let mut p = Parser { bytes : s.as_bytes(), pos : 0 };
+        let time = p.time()?;
+        if p.at_end() { Ok(time) } else { Err(ParseError::TrailingCharacters) }
+    }
+
+    /// Formats the Time using `strftime`-style specifiers, see `DateTime::format`
+    pub fn format<'a>(&self, fmt : &'a str) -> Formatted<'a> {
+        DateTime::new(Date::new(1970, 1, 1), *self).format(fmt)
+    }
+}
+
+impl DateTime {
+    pub fn new(date : Date, time : Time) -> DateTime {
+        DateTime { date, time }
+    }
+
+    pub fn date(&self) -> Date { self.date }
+    pub fn time(&self) -> Time { self.time }
+
+    pub fn is_valid(&self) -> bool {
+        self.date.is_valid() && self.time.is_valid()
+    }
+
+    /// Seconds since 1970-01-01 00:00:00
+    pub fn unix_time(&self) -> UnixTime {
+        UnixTime(timestamp(self))
+    }
+
+    /// Formats the Date & Time using `strftime`-style specifiers:
+    ///
+    /// `%Y` year, `%y` 2-digit year, `%m` month, `%d` day, `%e` space-padded day,
+    /// `%H` hour, `%I` 12-hour hour, `%M` minute, `%S` second, `%p` AM/PM,
+    /// `%A`/`%a` weekday name, `%B`/`%b` month name, `%j` day of the year,
+    /// `%u` weekday (Monday = 1), `%w` weekday (Sunday = 0),
+    /// `%F` = `%Y-%m-%d`, `%T` = `%H:%M:%S`, `%D` = `%m/%d/%y`, `%R` = `%H:%M`
+    /// and `%%` for a literal `%`. Unknown specifiers are printed as-is.
+    pub fn format<'a>(&self, fmt : &'a str) -> Formatted<'a> {
+        Formatted { dt : *self, fmt }
+    }
+
+    /// ISO 8601 representation, e.g. `2021-03-14T15:09:26`
+    pub fn iso8601(&self) -> Formatted<'static> {
+        self.format("%Y-%m-%dT%H:%M:%S")
+    }
+
+    /// RFC 3339 representation in UTC, e.g. `2021-03-14T15:09:26Z`
+    pub fn rfc3339(&self) -> Formatted<'static> {
+        self.format("%Y-%m-%dT%H:%M:%SZ")
+    }
+
+    /// Parses an ISO 8601 / RFC 3339 Date & Time.
+    ///
+    /// Accepts `YYYY-MM-DD`, followed by an optional `T` or space and `HH:MM[:SS[.fff]]`,
+    /// followed by an optional `Z` or `+HH:MM`/`-HH:MM` offset. Times with an offset are
+    /// converted to UTC, fractional seconds are discarded.
+    pub fn parse_iso8601(s : &str) -> Result<DateTime, ParseError> {
+        let mut p = Parser { bytes : s.as_bytes(), pos : 0 };
+
+        let year = p.number(4)?;
+        p.expect(b'-')?;
+        let month = p.number(2)?;
+        p.expect(b'-')?;
+        let day = p.number(2)?;
+
+        let date = Date::new(year as u16, month as u8, day as u8);
+        if !date.is_valid() { return Err(ParseError::OutOfRange) }
+
+        if p.at_end() {
+            return Ok(DateTime::new(date, Time::new(0, 0, 0)));
+        }
+
+        match p.next()? {
+            b'T' | b't' | b' ' => {}
+            _ => return Err(ParseError::InvalidCharacter(p.pos - 1))
+        }
+
+        let time = p.time()?;
+        let dt = DateTime::new(date, time);
+
+        let offset = match p.peek() {
+            None => 0,
+            Some(b'Z') | Some(b'z') => { p.pos += 1; 0 }
+            Some(sign @ b'+') | Some(sign @ b'-') => {
+                p.pos += 1;
+                let hours = p.number(2)? as i64;
+                if p.peek() == Some(b':') { p.pos += 1; }
+                let minutes = p.number(2)? as i64;
+                if hours > 23 || minutes > 59 { return Err(ParseError::OutOfRange) }
+                let offset = hours * 3600 + minutes * 60;
+                if sign == b'-' { -offset } else { offset }
+            }
+            Some(_) => return Err(ParseError::InvalidCharacter(p.pos))
+        };
+
+        if !p.at_end() { return Err(ParseError::TrailingCharacters) }
+
+        Ok(dt - Duration::seconds(offset))
+    }
+}
+
+/// A point in time, as seconds since 1970-01-01 00:00:00 UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct UnixTime(pub i64);
+
+/// A signed span of time, accurate to the second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Duration(i64);
+
+impl UnixTime {
+    pub const EPOCH : UnixTime = UnixTime(0);
+
+    pub fn from_secs(secs : i64) -> UnixTime {
+        UnixTime(secs)
+    }
+
+    pub fn as_secs(&self) -> i64 {
+        self.0
+    }
+
+    pub fn to_date_time(&self) -> DateTime {
+        from_timestamp(self.0)
+    }
+
+    pub fn date(&self) -> Date {
+        self.to_date_time().date
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        Weekday::from_monday((self.0.div_euclid(86400) + 3).rem_euclid(7) as u8)
+    }
+
+    /// Returns the Duration since an earlier point in time, negative if `earlier` is later
+    pub fn duration_since(&self, earlier : UnixTime) -> Duration {
+        *self - earlier
+    }
+}
+
+impl Duration {
+    pub const ZERO : Duration = Duration(0);
+
+    pub const fn seconds(seconds : i64) -> Duration { Duration(seconds) }
+    pub const fn minutes(minutes : i64) -> Duration { Duration(minutes * 60) }
+    pub const fn hours(hours : i64) -> Duration { Duration(hours * 3600) }
+    pub const fn days(days : i64) -> Duration { Duration(days * 86400) }
+    pub const fn weeks(weeks : i64) -> Duration { Duration(weeks * 7 * 86400) }
+
+    pub fn as_secs(&self) -> i64 { self.0 }
+    pub fn as_minutes(&self) -> i64 { self.0 / 60 }
+    pub fn as_hours(&self) -> i64 { self.0 / 3600 }
+    pub fn as_days(&self) -> i64 { self.0 / 86400 }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn abs(&self) -> Duration {
+        Duration(self.0.abs())
+    }
+}
+
+impl From<DateTime> for UnixTime {
+    fn from(dt : DateTime) -> UnixTime {
+        dt.unix_time()
+    }
+}
+
+impl From<UnixTime> for DateTime {
+    fn from(t : UnixTime) -> DateTime {
+        t.to_date_time()
+    }
+}
+
+impl Add<Duration> for UnixTime {
+    type Output = UnixTime;
+    fn add(self, rhs : Duration) -> UnixTime { UnixTime(self.0 + rhs.0) }
+}
+
+impl Sub<Duration> for UnixTime {
+    type Output = UnixTime;
+    fn sub(self, rhs : Duration) -> UnixTime { UnixTime(self.0 - rhs.0) }
+}
+
+impl Sub<UnixTime> for UnixTime {
+    type Output = Duration;
+    fn sub(self, rhs : UnixTime) -> Duration { Duration(self.0 - rhs.0) }
+}
+
+impl AddAssign<Duration> for UnixTime {
+    fn add_assign(&mut self, rhs : Duration) { self.0 += rhs.0 }
+}
+
+impl SubAssign<Duration> for UnixTime {
+    fn sub_assign(&mut self, rhs : Duration) { self.0 -= rhs.0 }
+}
+
+impl Add<Duration> for DateTime {
+    type Output = DateTime;
+    fn add(self, rhs : Duration) -> DateTime { (self.unix_time() + rhs).to_date_time() }
+}
+
+impl Sub<Duration> for DateTime {
+    type Output = DateTime;
+    fn sub(self, rhs : Duration) -> DateTime { (self.unix_time() - rhs).to_date_time() }
+}
+
+impl Sub<DateTime> for DateTime {
+    type Output = Duration;
+    fn sub(self, rhs : DateTime) -> Duration { self.unix_time() - rhs.unix_time() }
+}
+
+impl Add<Duration> for Date {
+    type Output = Date;
+    fn add(self, rhs : Duration) -> Date { Date::from_days_since_epoch(self.days_since_epoch() + rhs.as_days()) }
+}
+
+impl Sub<Duration> for Date {
+    type Output = Date;
+    fn sub(self, rhs : Duration) -> Date { Date::from_days_since_epoch(self.days_since_epoch() - rhs.as_days()) }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+    fn add(self, rhs : Duration) -> Duration { Duration(self.0 + rhs.0) }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+    fn sub(self, rhs : Duration) -> Duration { Duration(self.0 - rhs.0) }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+    fn neg(self) -> Duration { Duration(-self.0) }
+}
+
+impl Mul<i64> for Duration {
+    type Output = Duration;
+    fn mul(self, rhs : i64) -> Duration { Duration(self.0 * rhs) }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, rhs : Duration) { self.0 += rhs.0 }
+}
+
+impl SubAssign for Duration {
+    fn sub_assign(&mut self, rhs : Duration) { self.0 -= rhs.0 }
+}
+
+struct Parser<'a> {
+    bytes : &'a [u8],
+    pos   : usize,
+}
+
+impl<'a> Parser<'a> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, ParseError> {
+        let b = self.peek().ok_or(ParseError::TooShort)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn expect(&mut self, c : u8) -> Result<(), ParseError> {
+        if self.next()? == c {
+            Ok(())
+        } else {
+            Err(ParseError::InvalidCharacter(self.pos - 1))
+        }
+    }
+
+    /// Reads exactly `n` decimal digits
+    fn number(&mut self, n : usize) -> Result<u32, ParseError> {
+        let mut value = 0;
+        for _ in 0..n {
+            let b = self.next()?;
+            if !b.is_ascii_digit() { return Err(ParseError::InvalidCharacter(self.pos - 1)) }
+            value = value * 10 + (b - b'0') as u32;
+        }
+        Ok(value)
+    }
+
+    /// Reads `HH:MM[:SS[.fff]]`
+    fn time(&mut self) -> Result<Time, ParseError> {
+        let hour = self.number(2)?;
+        self.expect(b':')?;
+        let minute = self.number(2)?;
+        let mut second = 0;
+        if self.peek() == Some(b':') {
+            self.pos += 1;
+            second = self.number(2)?;
+            if self.peek() == Some(b'.') || self.peek() == Some(b',') {
+                self.pos += 1;
+                self.digits()?;
+            }
+        }
+
+        let time = Time::new(hour as u8, minute as u8, second as u8);
+        if time.is_valid() { Ok(time) } else { Err(ParseError::OutOfRange) }
+    }
+
+    /// Skips one or more decimal digits
+    fn digits(&mut self) -> Result<(), ParseError> {
+        let start = self.pos;
+        while self.peek().map_or(false, |b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start { Err(ParseError::TooShort) } else { Ok(()) }
+    }
+}
+
+impl FromStr for DateTime {
+    type Err = ParseError;
+
+    fn from_str(s : &str) -> Result<DateTime, ParseError> {
+        DateTime::parse_iso8601(s)
+    }
+}
+
+/// A Date & Time waiting to be formatted, returned by `DateTime::format`
+pub struct Formatted<'a> {
+    dt  : DateTime,
+    fmt : &'a str,
+}
+
+impl<'a> Display for Formatted<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let date = &self.dt.date;
+        let time = &self.dt.time;
+        let mut chars = self.fmt.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                write!(f, "{}", c)?;
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => write!(f, "{:04}", date.year)?,
+                Some('y') => write!(f, "{:02}", date.year % 100)?,
+                Some('m') => write!(f, "{:02}", date.month)?,
+                Some('d') => write!(f, "{:02}", date.day)?,
+                Some('e') => write!(f, "{:2}", date.day)?,
+                Some('H') => write!(f, "{:02}", time.hour)?,
+                Some('I') => write!(f, "{:02}", time.hour12())?,
+                Some('M') => write!(f, "{:02}", time.minute)?,
+                Some('S') => write!(f, "{:02}", time.second)?,
+                Some('p') => write!(f, "{}", if time.is_pm() { "PM" } else { "AM" })?,
+                Some('A') => write!(f, "{}", date.weekday().name())?,
+                Some('a') => write!(f, "{}", date.weekday().short_name())?,
+                Some('B') => write!(f, "{}", date.month_name())?,
+                Some('b') => write!(f, "{}", &date.month_name()[..3])?,
+                Some('j') => write!(f, "{:03}", date.ordinal())?,
+                Some('u') => write!(f, "{}", date.weekday().number_from_monday())?,
+                Some('w') => write!(f, "{}", date.weekday().number_from_sunday())?,
+                Some('F') => write!(f, "{:04}-{:02}-{:02}", date.year, date.month, date.day)?,
+                Some('T') => write!(f, "{:02}:{:02}:{:02}", time.hour, time.minute, time.second)?,
+                Some('D') => write!(f, "{:02}/{:02}/{:02}", date.month, date.day, date.year % 100)?,
+                Some('R') => write!(f, "{:02}:{:02}", time.hour, time.minute)?,
+                Some('%') => write!(f, "%")?,
+                Some(other) => write!(f, "%{}", other)?,
+                None => write!(f, "%")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for UnixTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Display for Duration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let secs = self.0.abs();
+        if secs >= 86400 {
+            write!(f, "{}{}d {:02}:{:02}:{:02}", sign, secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60, secs % 60)
+        } else {
+            write!(f, "{}{:02}:{:02}:{:02}", sign, secs / 3600, (secs % 3600) / 60, secs % 60)
+        }
+    }
+}
+
+impl Display for Weekday {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Display for ClockError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClockError::Parse(e) => write!(f, "{}", e),
+            ClockError::Rtc(e)   => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooShort               => write!(f, "input too short"),
+            ParseError::InvalidCharacter(pos)  => write!(f, "invalid character at {}", pos),
+            ParseError::OutOfRange             => write!(f, "field out of range"),
+            ParseError::TrailingCharacters     => write!(f, "trailing characters"),
+        }
+    }
+}
+
 impl Display for Date {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:02}/{:02}/{:04}",self.day, self.month, self.year)
@@ -104,7 +961,7 @@ impl Display for Date {
 
 impl Display for Time {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}:{}:{}",self.hour, self.minute, self.second)
+        write!(f, "{:02}:{:02}:{:02}",self.hour, self.minute, self.second)
     }
 }
 
@@ -112,4 +969,4 @@ impl Display for DateTime {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, "{} - {}", self.date, self.time)
     }
-}
\ No newline at end of file
+}
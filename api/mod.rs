@@ -1,2 +1,4 @@
 pub mod clock;
-pub mod sysinf;
\ No newline at end of file
+pub mod sysinf;
+pub mod timezone;
+mod wallclock;
\ No newline at end of file
@@ -1,5 +1,13 @@
-use crate::devices::cpu;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
 use raw_cpuid::{VendorInfo};
+
+use crate::devices::cpu;
+
+static USABLE_MEMORY : AtomicU64 = AtomicU64::new(0);
+static TOTAL_MEMORY : AtomicU64 = AtomicU64::new(0);
+
 /// Returns the CPU's vendor Information.
 pub fn cpu_vendor() -> Option<VendorInfo> {
     cpu::vendor_name()
@@ -8,4 +16,27 @@ pub fn cpu_vendor() -> Option<VendorInfo> {
 /// Returns the CPU's base frequency in Megahertz, or 0 if the frequency couldn't be attained.
 pub fn cpu_base_frequency() -> u16 {
     cpu::frequency().unwrap_or_default().processor_base_frequency()
-}
\ No newline at end of file
+}
+
+/// Bytes of physical memory the bootloader reported as usable, 0 before `init_modules`
+pub fn usable_memory() -> u64 {
+    USABLE_MEMORY.load(Ordering::Relaxed)
+}
+
+/// Bytes of physical memory in the bootloader's memory map, 0 before `init_modules`
+pub fn total_memory() -> u64 {
+    TOTAL_MEMORY.load(Ordering::Relaxed)
+}
+
+pub(crate) fn record_memory_map(memory_map : &MemoryMap) {
+    let (mut usable, mut total) = (0, 0);
+    for region in memory_map.iter() {
+        let size = region.range.end_addr() - region.range.start_addr();
+        total += size;
+        if region.region_type == MemoryRegionType::Usable {
+            usable += size;
+        }
+    }
+    USABLE_MEMORY.store(usable, Ordering::Relaxed);
+    TOTAL_MEMORY.store(total, Ordering::Relaxed);
+}
@@ -0,0 +1,126 @@
+use super::clock::{self, Date, DateTime, Weekday};
+
+/// Daylight-Saving rules, all of them shift the clock forward by one hour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DstRule {
+    /// No Daylight-Saving Time
+    None,
+    /// European Union: last Sunday of March to last Sunday of October, at 01:00 UTC
+    Eu,
+    /// United States: second Sunday of March to first Sunday of November, at 02:00 local time
+    Us,
+}
+
+/// A Time Zone, a fixed offset from UTC plus an optional Daylight-Saving rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeZone {
+    /// Offset from UTC in seconds, positive to the east of Greenwich
+    pub offset : i32,
+    pub dst    : DstRule,
+}
+
+const DST_SHIFT : i64 = 3600;
+
+impl TimeZone {
+    pub const UTC : TimeZone = TimeZone { offset : 0, dst : DstRule::None };
+
+    /// A Time Zone without Daylight-Saving, the offset is in minutes east of UTC
+    pub const fn fixed(offset_minutes : i32) -> TimeZone {
+        TimeZone { offset : offset_minutes * 60, dst : DstRule::None }
+    }
+
+    /// A Time Zone with Daylight-Saving, the offset is the standard offset in minutes east of UTC
+    pub const fn with_dst(offset_minutes : i32, dst : DstRule) -> TimeZone {
+        TimeZone { offset : offset_minutes * 60, dst }
+    }
+
+    /// Returns true if Daylight-Saving Time is in effect at the given UTC Date & Time
+    pub fn is_dst(&self, utc : &DateTime) -> bool {
+        self.is_dst_at(clock::timestamp(utc))
+    }
+
+    /// The total offset from UTC in seconds at the given UTC Date & Time
+    pub fn offset_at(&self, utc : &DateTime) -> i32 {
+        self.offset_at_timestamp(clock::timestamp(utc))
+    }
+
+    /// Converts a UTC Date & Time into this Time Zone
+    pub fn to_local(&self, utc : &DateTime) -> DateTime {
+        let ts = clock::timestamp(utc);
+        clock::from_timestamp(ts + self.offset_at_timestamp(ts) as i64)
+    }
+
+    /// Converts a Date & Time in this Time Zone into UTC.
+    ///
+    /// Local times skipped by a DST transition are treated as standard time,
+    /// local times repeated by a DST transition resolve to the DST instance.
+    pub fn to_utc(&self, local : &DateTime) -> DateTime {
+        let local_ts = clock::timestamp(local);
+        let standard = local_ts - self.offset as i64;
+        let daylight = standard - DST_SHIFT;
+        if self.dst != DstRule::None && self.is_dst_at(daylight) {
+            clock::from_timestamp(daylight)
+        } else {
+            clock::from_timestamp(standard)
+        }
+    }
+
+    fn offset_at_timestamp(&self, utc : i64) -> i32 {
+        if self.is_dst_at(utc) {
+            self.offset + DST_SHIFT as i32
+        } else {
+            self.offset
+        }
+    }
+
+    fn is_dst_at(&self, utc : i64) -> bool {
+        let year = clock::from_timestamp(utc + self.offset as i64).date.year;
+        match self.transitions(year) {
+            Some((start, end)) => utc >= start && utc < end,
+            None => false
+        }
+    }
+
+    /// UTC timestamps at which DST starts & ends in the given year
+    fn transitions(&self, year : u16) -> Option<(i64, i64)> {
+        let offset = self.offset as i64;
+        match self.dst {
+            DstRule::None => None,
+            DstRule::Eu => Some((
+                at(last_sunday(year, 3), 1),
+                at(last_sunday(year, 10), 1),
+            )),
+            DstRule::Us => Some((
+                at(nth_sunday(year, 3, 2), 2) - offset,
+                at(nth_sunday(year, 11, 1), 2) - offset - DST_SHIFT,
+            )),
+        }
+    }
+}
+
+impl Default for TimeZone {
+    fn default() -> TimeZone {
+        TimeZone::UTC
+    }
+}
+
+fn at(date : Date, hour : i64) -> i64 {
+    clock::timestamp(&DateTime::new(date, clock::Time::new(0, 0, 0))) + hour * 3600
+}
+
+fn last_sunday(year : u16, month : u8) -> Date {
+    let mut date = Date::new(year, month, clock::days_in_month(year, month));
+    while date.weekday() != Weekday::Sunday {
+        date.day -= 1;
+    }
+    date
+}
+
+fn nth_sunday(year : u16, month : u8, n : u8) -> Date {
+    let mut date = Date::new(year, month, 1);
+    while date.weekday() != Weekday::Sunday {
+        date.day += 1;
+    }
+    date.day += 7 * (n - 1);
+    date
+}
@@ -0,0 +1,134 @@
+// A software wall clock, read from the RTC once at boot and then advanced by the timer tick.
+// Queries only touch atomics, so they're cheap & safe to use from interrupt context.
+// Small drifts against the RTC are slewed out a little at a time, rather than stepped.
+// The RTC only reports whole seconds, so the clock is aligned to the moment they tick over.
+
+use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+
+use crate::interrupts::pit;
+
+/// Nanoseconds since 1970-01-01 00:00:00 UTC
+static WALL_NS : AtomicI64 = AtomicI64::new(0);
+static SYNCED : AtomicBool = AtomicBool::new(false);
+
+/// Correction still to be applied, in nanoseconds
+static SLEW_NS : AtomicI64 = AtomicI64::new(0);
+
+static TICKS_SINCE_SYNC : AtomicU64 = AtomicU64::new(0);
+static RESYNC_PENDING : AtomicBool = AtomicBool::new(false);
+
+/// The RTC seconds last read while waiting for them to tick over, `NO_SAMPLE` if there's no reading
+static LAST_RTC_SECS : AtomicI64 = AtomicI64::new(NO_SAMPLE);
+/// The clock's time when `LAST_RTC_SECS` was read
+static LAST_SAMPLE_NS : AtomicI64 = AtomicI64::new(0);
+const NO_SAMPLE : i64 = i64::MIN;
+
+const NS_PER_SEC : i64 = 1_000_000_000;
+
+/// How often the clock is compared against the RTC
+const RESYNC_INTERVAL_SECS : u64 = 64;
+/// Offsets larger than this are stepped instead of slewed
+const MAX_SLEW_NS : i64 = 2_000_000_000;
+/// At most 1/SLEW_DIVISOR of each tick is used to correct the clock
+const SLEW_DIVISOR : i64 = 20;
+
+/// The PIT reload value when the tick rate was never set
+const DEFAULT_RELOAD : u64 = 65536;
+
+/// Length of one timer tick in nanoseconds, using the PIT's actual (integer) reload value
+fn tick_ns() -> i64 {
+    let freq = crate::get_frequency();
+    let reload = if freq == 0 { DEFAULT_RELOAD } else { (pit::FREQUENCY / freq) as u64 };
+    (reload * 1_000_000_000 / pit::FREQUENCY as u64) as i64
+}
+
+fn ticks_per_second() -> u64 {
+    (1_000_000_000 / tick_ns().max(1)) as u64
+}
+
+/// Advances the clock by one tick, called from the timer interrupt
+pub(crate) fn tick() {
+    if !SYNCED.load(Ordering::Acquire) {
+        return;
+    }
+
+    let step = tick_ns();
+    let limit = step / SLEW_DIVISOR;
+    let slew = SLEW_NS.load(Ordering::Relaxed);
+    let correction = slew.max(-limit).min(limit);
+    if correction != 0 {
+        SLEW_NS.fetch_sub(correction, Ordering::Relaxed);
+    }
+    WALL_NS.fetch_add(step + correction, Ordering::Release);
+
+    let ticks = TICKS_SINCE_SYNC.fetch_add(1, Ordering::Relaxed) + 1;
+    if ticks >= RESYNC_INTERVAL_SECS * ticks_per_second() {
+        RESYNC_PENDING.store(true, Ordering::Relaxed);
+    }
+}
+
+pub(crate) fn is_synced() -> bool {
+    SYNCED.load(Ordering::Acquire)
+}
+
+/// Nanoseconds since the Unix Epoch
+pub(crate) fn now_ns() -> i64 {
+    WALL_NS.load(Ordering::Acquire)
+}
+
+/// Sets the clock, discarding any pending slew
+pub(crate) fn step_to(ns : i64) {
+    SLEW_NS.store(0, Ordering::Relaxed);
+    WALL_NS.store(ns, Ordering::Release);
+    TICKS_SINCE_SYNC.store(0, Ordering::Relaxed);
+    LAST_RTC_SECS.store(NO_SAMPLE, Ordering::Relaxed);
+    SYNCED.store(true, Ordering::Release);
+}
+
+/// Compares the clock against a reading of the RTC, which is only accurate to the second.
+///
+/// When the reading is one second past the previous one, taken at most two ticks before,
+/// the RTC ticked over in between: the clock is corrected against that edge & the resync is done.
+/// Otherwise a clock outside the second read is pulled into its middle.
+/// Small offsets are slewed and large ones are stepped.
+pub(crate) fn resync(rtc_secs : i64) {
+    if !is_synced() {
+        step_to(rtc_secs * NS_PER_SEC + NS_PER_SEC / 2);
+    }
+
+    let now = now_ns();
+    let last_secs = LAST_RTC_SECS.swap(rtc_secs, Ordering::Relaxed);
+    let last_ns = LAST_SAMPLE_NS.swap(now, Ordering::Relaxed);
+
+    if last_secs != NO_SAMPLE && rtc_secs == last_secs + 1 && now - last_ns <= 2 * tick_ns() {
+        let edge = last_ns + (now - last_ns) / 2;
+        correct(now, rtc_secs * NS_PER_SEC - edge);
+        TICKS_SINCE_SYNC.store(0, Ordering::Relaxed);
+        RESYNC_PENDING.store(false, Ordering::Relaxed);
+        LAST_RTC_SECS.store(NO_SAMPLE, Ordering::Relaxed);
+        return;
+    }
+
+    let low = rtc_secs * NS_PER_SEC;
+    if now < low || now >= low + NS_PER_SEC {
+        correct(now, low + NS_PER_SEC / 2 - now);
+    }
+}
+
+fn correct(now : i64, offset : i64) {
+    if offset.abs() > MAX_SLEW_NS {
+        step_to(now + offset);
+    } else {
+        SLEW_NS.store(offset, Ordering::Relaxed);
+    }
+}
+
+/// Asks for the clock to be compared against the RTC until its next second edge is seen
+pub(crate) fn request_resync() {
+    RESYNC_PENDING.store(true, Ordering::Relaxed);
+}
+
+/// Returns true while the clock should be compared against the RTC
+pub(crate) fn resync_pending() -> bool {
+    RESYNC_PENDING.load(Ordering::Relaxed)
+}
@@ -2,11 +2,37 @@
 
 use x86_64::instructions::port::Port;
 use x86_64::instructions::interrupts;
+use core::sync::atomic::{AtomicU8, Ordering};
 
 const CMOS_ADDR : u16 = 0x70;
 const CMOS_DATA : u16 = 0x71;
 
-const CMOS_UPDATE_STATE : u8 = 0xA0;
+/// First & last CMOS indices past the RTC's registers
+pub const NVRAM_START : u8 = 0x0E;
+pub const NVRAM_END   : u8 = 0x7F;
+
+/// Century assumed when the ACPI FADT doesn't provide a century register
+const DEFAULT_CENTURY : u16 = 20;
+const MAX_READ_ATTEMPTS : usize = 8;
+const MAX_UPDATE_WAIT : usize = 1_000_000;
+
+/// CMOS index of the century register, 0 if there isn't one
+static CENTURY_REGISTER : AtomicU8 = AtomicU8::new(0);
+
+/// Sets the CMOS index of the century register, as given by the `century` field of the ACPI FADT.
+/// An index of 0 means there's no century register and the 21st century is assumed.
+/// Returns false, leaving the index unchanged, if it isn't 0 or an NVRAM index.
+pub fn set_century_register(index : u8) -> bool {
+    if index != 0 && (index < NVRAM_START || index > NVRAM_END) {
+        return false;
+    }
+    CENTURY_REGISTER.store(index, Ordering::Relaxed);
+    true
+}
+
+pub fn century_register() -> u8 {
+    CENTURY_REGISTER.load(Ordering::Relaxed)
+}
 #[repr(u8)]
 enum Register {
     Second = 0x00,
@@ -18,8 +44,12 @@ enum Register {
     A = 0x0A,
     B = 0x0B,
     C = 0x0C,
+    D = 0x0D,
 }
 
+/// Bit of the index port that masks NMIs while it's set
+const NMI_DISABLE : u8 = 0x80;
+
 #[repr(u8)]
 enum Interrupt {
     Periodic = 1 << 6,
@@ -27,7 +57,7 @@ enum Interrupt {
     Update = 1 << 4,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RTC {
     pub year: u16,
     pub month: u8,
@@ -37,67 +67,219 @@ pub struct RTC {
     pub second: u8,
 }
 
+/// Errors returned when accessing the Real-Time Clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcError {
+    /// A Date or Time field is outside of its valid range
+    InvalidValue,
+    /// The RTC kept updating, so a consistent value couldn't be read
+    Unstable,
+}
+
+/// The time registers exactly as read from the CMOS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RawRtc {
+    second  : u8,
+    minute  : u8,
+    hour    : u8,
+    day     : u8,
+    month   : u8,
+    year    : u8,
+    century : u8,
+}
+
+impl RTC {
+    /// Returns true if every field is within its valid range
+    pub fn is_valid(&self) -> bool {
+        let days_in_month = match self.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year(self.year) => 29,
+            2 => 28,
+            _ => return false
+        };
+        self.day >= 1 && self.day <= days_in_month
+            && self.hour < 24 && self.minute < 60 && self.second < 60
+    }
+}
+
+fn is_leap_year(year : u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
 
 impl CMOS {
     pub fn new() -> CMOS {
         CMOS {
             addr : Port::new(CMOS_ADDR),
-            data : Port::new(CMOS_DATA)
+            data : Port::new(CMOS_DATA),
+            nmi_disabled : false,
         }
     }
 
-    pub fn rtc(&mut self) -> RTC {
-        while self.is_updating() {
-            x86_64::instructions::hlt();
+    /// Reads the Date & Time from the RTC.
+    ///
+    /// The registers are read until two consecutive reads agree, so a read straddling
+    /// an update never returns a mix of old & new values.
+    pub fn rtc(&mut self) -> Result<RTC, RtcError> {
+        let mut last = self.read_raw()?;
+        for _ in 0..MAX_READ_ATTEMPTS {
+            let current = self.read_raw()?;
+            if current == last {
+                return self.decode(current);
+            }
+            last = current;
         }
+        Err(RtcError::Unstable)
+    }
 
-        let mut second = self.read_register(Register::Second);
-        let mut minute = self.read_register(Register::Minute);
-        let mut hour = self.read_register(Register::Hour);
-        let mut day = self.read_register(Register::Day);
-        let mut month = self.read_register(Register::Month);
-        let mut year = self.read_register(Register::Year) as u16;
+    /// Reads the raw time registers once an update isn't in progress
+    fn read_raw(&mut self) -> Result<RawRtc, RtcError> {
+        self.wait_for_update()?;
+        let century = century_register();
+        Ok(RawRtc {
+            second  : self.read_register(Register::Second),
+            minute  : self.read_register(Register::Minute),
+            hour    : self.read_register(Register::Hour),
+            day     : self.read_register(Register::Day),
+            month   : self.read_register(Register::Month),
+            year    : self.read_register(Register::Year),
+            century : if century != 0 { self.read_index(century) } else { 0 },
+        })
+    }
 
+    fn decode(&mut self, raw : RawRtc) -> Result<RTC, RtcError> {
         let b = self.read_register(Register::B);
+        let binary = b & 0x04 != 0;
+        let convert = |value : u8| if binary { value } else { (value & 0x0F) + ((value >> 4) * 10) };
 
-        if b & 0x04 == 0 { // BCD Mode
-            second = (second & 0x0F) + ((second / 16) * 10);
-            minute = (minute & 0x0F) + ((minute / 16) * 10);
-            hour = ((hour & 0x0F) + (((hour & 0x70) / 16) * 10)) | (hour & 0x80);
-            day = (day & 0x0F) + ((day / 16) * 10);
-            month = (month & 0x0F) + ((month / 16) * 10);
-            year = (year & 0x0F) + ((year / 16) * 10);
+        let pm = raw.hour & 0x80 != 0;
+        let mut hour = convert(raw.hour & 0x7F);
+        if b & 0x02 == 0 { // 12 hour format
+            if hour < 1 || hour > 12 { return Err(RtcError::InvalidValue) }
+            hour = match (hour, pm) {
+                (12, false) => 0,
+                (12, true)  => 12,
+                (h, false)  => h,
+                (h, true)   => h + 12,
+            };
         }
 
-        if (b & 0x02 == 0) && (hour & 0x80 == 0) { // 12 hour format
-            hour = ((hour & 0x7F) + 12) % 24;
+        let year = convert(raw.year) as u16;
+        let century = if century_register() != 0 {
+            convert(raw.century) as u16
+        } else {
+            DEFAULT_CENTURY
+        };
+        if century > 99 {
+            return Err(RtcError::InvalidValue);
         }
 
-        year += 2000; // TODO: Don't forget to change this next century
-
-        RTC {
-            day,
-            month,
-            year,
+        let rtc = RTC {
+            second : convert(raw.second),
+            minute : convert(raw.minute),
             hour,
-            minute,
-            second
+            day    : convert(raw.day),
+            month  : convert(raw.month),
+            year   : century * 100 + year,
+        };
+
+        if year <= 99 && rtc.is_valid() { Ok(rtc) } else { Err(RtcError::InvalidValue) }
+    }
+
+    fn wait_for_update(&mut self) -> Result<(), RtcError> {
+        for _ in 0..MAX_UPDATE_WAIT {
+            if !self.is_updating() {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(RtcError::Unstable)
+    }
+
+    /// Writes the Date & Time into the RTC, honouring its BCD & 12-hour modes.
+    /// Without a century register only years 2000 - 2099 can be stored.
+    pub fn set_rtc(&mut self, rtc : RTC) -> Result<(), RtcError> {
+        let century = century_register();
+        let century_ok = if century != 0 { rtc.year <= 9999 } else { rtc.year / 100 == DEFAULT_CENTURY };
+        if !rtc.is_valid() || !century_ok {
+            return Err(RtcError::InvalidValue);
         }
 
+        interrupts::without_interrupts(|| {
+            self.wait_for_update()?;
+
+            self.disable_nmi();
+            let b = self.read_register(Register::B);
+            // Halt updates while the registers are written
+            self.write_register(Register::B, b | 0x80);
+
+            let binary = b & 0x04 != 0;
+            let encode = |value : u8| if binary { value } else { ((value / 10) << 4) | (value % 10) };
+
+            let hour = if b & 0x02 == 0 { // 12 hour format
+                let pm = if rtc.hour >= 12 { 0x80 } else { 0 };
+                let hour12 = match rtc.hour % 12 { 0 => 12, h => h };
+                encode(hour12) | pm
+            } else {
+                encode(rtc.hour)
+            };
+
+            self.write_register(Register::Second, encode(rtc.second));
+            self.write_register(Register::Minute, encode(rtc.minute));
+            self.write_register(Register::Hour, hour);
+            self.write_register(Register::Day, encode(rtc.day));
+            self.write_register(Register::Month, encode(rtc.month));
+            self.write_register(Register::Year, encode((rtc.year % 100) as u8));
+            if century != 0 {
+                self.write_index(century, encode((rtc.year / 100) as u8));
+            }
+
+            self.write_register(Register::B, b & !0x80);
+            self.enable_nmi();
+            Ok(())
+        })
     }
 
     fn is_updating(&mut self) -> bool {
-        unsafe {
-            self.addr.write(CMOS_UPDATE_STATE);
-            (self.data.read() & 0x80 as u8) == 1
-        }
+        self.read_register(Register::A) & 0x80 != 0
     }
 
     fn read_register(&mut self, reg : Register) -> u8 {
-        unsafe {
-            self.addr.write(reg as u8);
-            self.data.read()
-        }
+        self.read_index(reg as u8)
+    }
+
+    fn write_register(&mut self, reg : Register, value : u8) {
+        self.write_index(reg as u8, value)
+    }
+
+    /// Selects a register, the NMI mask bit shares the index port so it's written every time
+    fn select(&mut self, index : u8) {
+        let nmi = if self.nmi_disabled { NMI_DISABLE } else { 0 };
+        unsafe { self.addr.write(index | nmi) }
+    }
+
+    fn read_index(&mut self, index : u8) -> u8 {
+        self.select(index);
+        unsafe { self.data.read() }
+    }
+
+    fn write_index(&mut self, index : u8, value : u8) {
+        self.select(index);
+        unsafe { self.data.write(value) }
+    }
+
+    /// Reads a byte of battery-backed NVRAM, indices below 0x0E are the RTC's registers
+    pub fn read_nvram(&mut self, index : u8) -> Option<u8> {
+        if index < NVRAM_START || index > NVRAM_END { return None }
+        Some(interrupts::without_interrupts(|| self.read_index(index)))
+    }
+
+    /// Writes a byte of battery-backed NVRAM, returning false if the index isn't NVRAM
+    pub fn write_nvram(&mut self, index : u8, value : u8) -> bool {
+        if index < NVRAM_START || index > NVRAM_END { return false }
+        interrupts::without_interrupts(|| self.write_index(index, value));
+        true
     }
 
     pub fn enable_periodic_interrupt(&mut self) {
@@ -117,12 +299,8 @@ impl CMOS {
     pub fn set_periodic_interrupt_rate(&mut self, rate: u8) {
         interrupts::without_interrupts(|| {
             self.disable_nmi();
-            unsafe {
-                self.addr.write(Register::A as u8);
-                let prev = self.data.read();
-                self.addr.write(Register::A as u8);
-                self.data.write((prev & 0xF0) | rate);
-            }
+            let prev = self.read_register(Register::A);
+            self.write_register(Register::A, (prev & 0xF0) | rate);
             self.enable_nmi();
             self.notify_end_of_interrupt();
         });
@@ -131,42 +309,34 @@ impl CMOS {
     fn enable_interrupt(&mut self, interrupt: Interrupt) {
         interrupts::without_interrupts(|| {
             self.disable_nmi();
-            unsafe {
-                self.addr.write(Register::B as u8);
-                let prev = self.data.read();
-                self.addr.write(Register::B as u8);
-                self.data.write(prev | interrupt as u8);
-            }
+            let prev = self.read_register(Register::B);
+            self.write_register(Register::B, prev | interrupt as u8);
             self.enable_nmi();
             self.notify_end_of_interrupt();
         });
     }
 
     pub fn notify_end_of_interrupt(&mut self) {
-        unsafe {
-            self.addr.write(Register::C as u8);
-            self.data.read();
-        }
+        self.read_register(Register::C);
     }
 
+    // The index port can't be reliably read back, so the NMI mask is tracked in `nmi_disabled`
     fn enable_nmi(&mut self) {
-        unsafe {
-            let prev = self.addr.read();
-            self.addr.write(prev & 0x7F);
-        }
+        self.nmi_disabled = false;
+        self.select(Register::D as u8);
     }
 
     fn disable_nmi(&mut self) {
-        unsafe {
-            let prev = self.addr.read();
-            self.addr.write(prev | 0x80);
-        }
+        self.nmi_disabled = true;
+        self.select(Register::D as u8);
     }
 }
 
 pub struct CMOS {
     addr : Port<u8>,
-    data : Port<u8>
+    data : Port<u8>,
+    /// Set while NMIs are masked, every index written keeps them masked
+    nmi_disabled : bool,
 }
 
 
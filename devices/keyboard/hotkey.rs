@@ -0,0 +1,335 @@
+// Global hotkeys, matched in the keyboard interrupt so they keep working when the
+// foreground program is stuck. A matched combo never reaches the scancode queue.
+
+use pc_keyboard::{DecodeState, ScancodeSet, ScancodeSet1, ScancodeSet2};
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{KeyCode, ScancodeSetId};
+
+const MAX_HOTKEYS : usize = 32;
+
+/// Called from the keyboard interrupt, so it mustn't block on locks the interrupted code may hold
+pub type HotkeyAction = fn();
+
+/// A key plus the modifiers that must be held with it, e.g. `Hotkey::new(KeyCode::DEL).ctrl().alt()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkey {
+    pub code  : KeyCode,
+    pub ctrl  : bool,
+    pub alt   : bool,
+    pub shift : bool,
+    pub meta  : bool,
+}
+
+impl Hotkey {
+    pub const fn new(code : KeyCode) -> Hotkey {
+        Hotkey { code, ctrl : false, alt : false, shift : false, meta : false }
+    }
+
+    pub const fn ctrl(self) -> Hotkey {
+        Hotkey { ctrl : true, ..self }
+    }
+
+    pub const fn alt(self) -> Hotkey {
+        Hotkey { alt : true, ..self }
+    }
+
+    pub const fn shift(self) -> Hotkey {
+        Hotkey { shift : true, ..self }
+    }
+
+    pub const fn meta(self) -> Hotkey {
+        Hotkey { meta : true, ..self }
+    }
+}
+
+/// Errors returned by `register_hotkey`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyError {
+    AlreadyRegistered,
+    Full,
+}
+
+static HOTKEYS : Mutex<[Option<(Hotkey, HotkeyAction)>; MAX_HOTKEYS]> = Mutex::new([None; MAX_HOTKEYS]);
+
+/// Runs `action` whenever `combo` is pressed, instead of passing the key on
+pub fn register_hotkey(combo : Hotkey, action : HotkeyAction) -> Result<(), HotkeyError> {
+    without_interrupts(|| {
+        let mut hotkeys = HOTKEYS.lock();
+        if hotkeys.iter().flatten().any(|(hotkey, _)| *hotkey == combo) {
+            return Err(HotkeyError::AlreadyRegistered);
+        }
+        match hotkeys.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => { *slot = Some((combo, action)); Ok(()) }
+            None => Err(HotkeyError::Full)
+        }
+    })
+}
+
+/// Removes a hotkey, returning true if it was registered
+pub fn unregister_hotkey(combo : Hotkey) -> bool {
+    without_interrupts(|| {
+        let mut hotkeys = HOTKEYS.lock();
+        match hotkeys.iter_mut().find(|slot| matches!(slot, Some((hotkey, _)) if *hotkey == combo)) {
+            Some(slot) => { *slot = None; true }
+            None => false
+        }
+    })
+}
+
+/// Follows the raw scancodes in the interrupt handler, independently of the readers' decoder
+struct Matcher {
+    set          : ScancodeSetId,
+    decode_state : DecodeState,
+    /// Prefix bytes (e.g. E0, F0) held back until we know whether their key is a hotkey
+    pending      : [u8; 2],
+    pending_len  : usize,
+    ctrl         : u8,
+    alt          : u8,
+    shift        : u8,
+    meta         : u8,
+    /// Keys whose press triggered a hotkey, their repeats & release are swallowed too
+    swallowed    : [u64; 4],
+}
+
+const LEFT : u8 = 1;
+const RIGHT : u8 = 2;
+
+impl Matcher {
+    fn flush(&mut self, pass : &mut dyn FnMut(u8)) {
+        for &byte in &self.pending[..self.pending_len] {
+            pass(byte);
+        }
+        self.pending_len = 0;
+    }
+
+    fn update_modifiers(&mut self, code : pc_keyboard::KeyCode, down : bool) {
+        let (modifier, side) = match code {
+            pc_keyboard::KeyCode::ControlLeft  => (&mut self.ctrl, LEFT),
+            pc_keyboard::KeyCode::ControlRight => (&mut self.ctrl, RIGHT),
+            pc_keyboard::KeyCode::AltLeft      => (&mut self.alt, LEFT),
+            pc_keyboard::KeyCode::AltRight     => (&mut self.alt, RIGHT),
+            pc_keyboard::KeyCode::ShiftLeft    => (&mut self.shift, LEFT),
+            pc_keyboard::KeyCode::ShiftRight   => (&mut self.shift, RIGHT),
+            pc_keyboard::KeyCode::WindowsLeft  => (&mut self.meta, LEFT),
+            pc_keyboard::KeyCode::WindowsRight => (&mut self.meta, RIGHT),
+            _ => return
+        };
+        if down { *modifier |= side } else { *modifier &= !side }
+    }
+
+    fn combo(&self, code : KeyCode) -> Hotkey {
+        Hotkey {
+            code,
+            ctrl  : self.ctrl != 0,
+            alt   : self.alt != 0,
+            shift : self.shift != 0,
+            meta  : self.meta != 0,
+        }
+    }
+
+    /// Index & bit of a key in `swallowed`
+    fn slot(code : pc_keyboard::KeyCode) -> (usize, u64) {
+        let index = code as usize;
+        (index / 64 % 4, 1 << (index % 64))
+    }
+}
+
+static MATCHER : Mutex<Matcher> = Mutex::new(Matcher {
+    set          : ScancodeSetId::Set1,
+    decode_state : DecodeState::Start,
+    pending      : [0; 2],
+    pending_len  : 0,
+    ctrl : 0, alt : 0, shift : 0, meta : 0,
+    swallowed    : [0; 4],
+});
+
+/// Checks a scancode from the keyboard interrupt against the hotkeys,
+/// passing on the bytes that aren't part of one
+pub(crate) fn filter_scancode(scancode : u8, pass : &mut dyn FnMut(u8)) {
+    let mut matcher = match MATCHER.try_lock() {
+        Some(matcher) => matcher,
+        None => return pass(scancode)
+    };
+
+    let result = match matcher.set {
+        ScancodeSetId::Set1 => ScancodeSet1::advance_state(&mut matcher.decode_state, scancode),
+        ScancodeSetId::Set2 => ScancodeSet2::advance_state(&mut matcher.decode_state, scancode),
+    };
+    let event = match result {
+        Ok(Some(event)) => event,
+        Ok(None) if matcher.pending_len < matcher.pending.len() => {
+            let len = matcher.pending_len;
+            matcher.pending[len] = scancode;
+            matcher.pending_len += 1;
+            return;
+        }
+        _ => {
+            matcher.flush(pass);
+            return pass(scancode);
+        }
+    };
+
+    let down = event.state == pc_keyboard::KeyState::Down;
+    matcher.update_modifiers(event.code, down);
+    let (word, bit) = Matcher::slot(event.code);
+
+    if !down {
+        if matcher.swallowed[word] & bit != 0 {
+            matcher.swallowed[word] &= !bit;
+            matcher.pending_len = 0;
+        } else {
+            matcher.flush(pass);
+            pass(scancode);
+        }
+        return;
+    }
+
+    if matcher.swallowed[word] & bit != 0 {
+        // Held down, only run the action once
+        matcher.pending_len = 0;
+        return;
+    }
+
+    let combo = matcher.combo(KeyCode::from_pc_keycode(event.code));
+    let action = match HOTKEYS.try_lock() {
+        Some(hotkeys) => hotkeys.iter().flatten().find(|(hotkey, _)| *hotkey == combo).map(|(_, action)| *action),
+        None => None
+    };
+
+    match action {
+        Some(action) => {
+            matcher.swallowed[word] |= bit;
+            matcher.pending_len = 0;
+            drop(matcher);
+            action();
+        }
+        None => {
+            matcher.flush(pass);
+            pass(scancode);
+        }
+    }
+}
+
+/// Passes on release scancodes for the modifiers held down, which stay held as far as
+/// hotkeys are concerned. Called from the keyboard interrupt when the console changes.
+pub(crate) fn release_held_modifiers(pass : &mut dyn FnMut(u8)) {
+    let matcher = match MATCHER.try_lock() {
+        Some(matcher) => matcher,
+        None => return
+    };
+    let held = [
+        (matcher.ctrl, pc_keyboard::KeyCode::ControlLeft, pc_keyboard::KeyCode::ControlRight),
+        (matcher.alt, pc_keyboard::KeyCode::AltLeft, pc_keyboard::KeyCode::AltRight),
+        (matcher.shift, pc_keyboard::KeyCode::ShiftLeft, pc_keyboard::KeyCode::ShiftRight),
+        (matcher.meta, pc_keyboard::KeyCode::WindowsLeft, pc_keyboard::KeyCode::WindowsRight),
+    ];
+    for &(sides, left, right) in held.iter() {
+        if sides & LEFT != 0 {
+            super::inject::key_scancodes(left, false, matcher.set, pass);
+        }
+        if sides & RIGHT != 0 {
+            super::inject::key_scancodes(right, false, matcher.set, pass);
+        }
+    }
+}
+
+/// Follows another scancode set, called when the readers' decoder switches
+pub(crate) fn set_scancode_set(set : ScancodeSetId) {
+    without_interrupts(|| {
+        let mut matcher = MATCHER.lock();
+        matcher.set = set;
+        matcher.decode_state = DecodeState::Start;
+        matcher.pending_len = 0;
+    });
+}
+
+// Default Hotkeys ======================================================================
+
+/// Registers the built in hotkeys:
+///
+/// - Ctrl+Alt+Del reboots
+/// - Print Screen dumps the screen to serial
+/// - Ctrl+Alt+Shift+T/I/M dump the tasks, interrupt counts & memory to serial
+/// - Shift+PageUp/PageDown scroll the terminal through its scrollback
+/// - Alt+F1..F6 switch virtual consoles
+pub fn register_defaults() {
+    let _ = register_hotkey(Hotkey::new(KeyCode::DEL).ctrl().alt(), reboot);
+    let _ = register_hotkey(Hotkey::new(KeyCode::PRINT_SCREEN), print_screen);
+    let _ = register_hotkey(Hotkey::new(KeyCode::KEY_T).ctrl().alt().shift(), dump_tasks);
+    let _ = register_hotkey(Hotkey::new(KeyCode::KEY_I).ctrl().alt().shift(), dump_interrupts);
+    let _ = register_hotkey(Hotkey::new(KeyCode::KEY_M).ctrl().alt().shift(), show_memory);
+    let _ = register_hotkey(Hotkey::new(KeyCode::PAGE_UP).shift(), scroll_back);
+    let _ = register_hotkey(Hotkey::new(KeyCode::PAGE_DOWN).shift(), scroll_forward);
+
+    let switches : [(KeyCode, HotkeyAction); crate::io::vconsole::CONSOLE_COUNT] = [
+        (KeyCode::F1, || { crate::io::vconsole::switch_to(0); }),
+        (KeyCode::F2, || { crate::io::vconsole::switch_to(1); }),
+        (KeyCode::F3, || { crate::io::vconsole::switch_to(2); }),
+        (KeyCode::F4, || { crate::io::vconsole::switch_to(3); }),
+        (KeyCode::F5, || { crate::io::vconsole::switch_to(4); }),
+        (KeyCode::F6, || { crate::io::vconsole::switch_to(5); }),
+    ];
+    for (key, action) in switches.iter() {
+        let _ = register_hotkey(Hotkey::new(*key).alt(), *action);
+    }
+}
+
+fn reboot() {
+    crate::devices::ps2::reboot();
+}
+
+fn print_screen() {
+    let mut buffer = match crate::gfx::vga::GLOBAL_VGA_BUFFER.try_lock() {
+        Some(buffer) => buffer,
+        None => return
+    };
+    crate::serial_println!("--- Screen ---");
+    for y in 0..crate::gfx::vga::SCREEN_HEIGHT {
+        let mut line = [b' '; crate::gfx::vga::SCREEN_WIDTH];
+        for (x, byte) in line.iter_mut().enumerate() {
+            let chr = buffer.get_char(x, y).code_point;
+            *byte = if chr.is_ascii_graphic() { chr } else { b' ' };
+        }
+        let end = line.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+        crate::serial_println!("{}", core::str::from_utf8(&line[..end]).unwrap_or(""));
+    }
+    crate::serial_println!("--------------");
+}
+
+fn dump_tasks() {
+    crate::serial_println!("Tasks: {} running, {} spawned since boot",
+        crate::task::live_tasks(), crate::task::spawned_tasks());
+}
+
+fn dump_interrupts() {
+    crate::serial_println!("Interrupts:");
+    for irq in 0..16 {
+        let count = crate::interrupts::irq_count(irq);
+        if count != 0 {
+            crate::serial_println!("  IRQ {:>2}: {}", irq, count);
+        }
+    }
+    crate::serial_println!("  Dropped Scancodes: {}", super::dropped_scancodes());
+}
+
+fn show_memory() {
+    crate::serial_println!("Memory: {} KiB usable of {} KiB",
+        crate::api::sysinf::usable_memory() / 1024, crate::api::sysinf::total_memory() / 1024);
+}
+
+/// Rows moved by Shift+PageUp/PageDown, a screen less one row of overlap
+const SCROLL_PAGE : usize = crate::gfx::vga::SCREEN_HEIGHT - 1;
+
+fn scroll_back() {
+    if let Some(mut term) = crate::io::vconsole::active_terminal().try_lock() {
+        term.scroll_back(SCROLL_PAGE);
+    }
+}
+
+fn scroll_forward() {
+    if let Some(mut term) = crate::io::vconsole::active_terminal().try_lock() {
+        term.scroll_forward(SCROLL_PAGE);
+    }
+}
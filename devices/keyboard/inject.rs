@@ -0,0 +1,168 @@
+// Synthesises scancodes for keys & characters, so input can be fed without a keyboard.
+// Characters are mapped to keys using the US layout.
+
+use pc_keyboard::{DecodedKey, KeyCode as RawKeyCode};
+use x86_64::instructions::interrupts::without_interrupts;
+
+use super::{add_scancode, scancode_set, ScancodeSetId};
+
+/// Prefix of extended keys in both sets
+const EXTENDED : u8 = 0xE0;
+/// Prefix of key releases in set 2, set 1 sets the top bit instead
+const SET2_RELEASE : u8 = 0xF0;
+
+/// Feeds a scancode in as if the keyboard had sent it, hotkeys included
+pub fn inject_scancode(scancode : u8) {
+    add_scancode(scancode);
+}
+
+/// Presses or releases a key. Returns false if there's no scancode for it.
+pub fn inject_keycode(code : RawKeyCode, down : bool) -> bool {
+    let set = scancode_set();
+    // Keeps the keyboard interrupt from splitting up an extended or release sequence
+    without_interrupts(|| key_scancodes(code, down, set, &mut inject_scancode))
+}
+
+/// Passes on the scancodes that press or release a key in a scancode set.
+/// Returns false if there's no scancode for it.
+pub(super) fn key_scancodes(code : RawKeyCode, down : bool, set : ScancodeSetId, pass : &mut dyn FnMut(u8)) -> bool {
+    let (extended, set1, set2) = match scancodes(code) {
+        Some(codes) => codes,
+        None => return false
+    };
+
+    if extended {
+        pass(EXTENDED);
+    }
+    match set {
+        ScancodeSetId::Set1 => pass(if down { set1 } else { set1 | 0x80 }),
+        ScancodeSetId::Set2 => {
+            if !down {
+                pass(SET2_RELEASE);
+            }
+            pass(set2);
+        }
+    }
+    true
+}
+
+/// Presses & releases a key, holding the given modifiers around it
+pub fn inject_combo(code : RawKeyCode, modifiers : &[RawKeyCode]) -> bool {
+    if scancodes(code).is_none() {
+        return false;
+    }
+    // Keys typed meanwhile would otherwise pick up the held modifiers
+    without_interrupts(|| {
+        for &modifier in modifiers {
+            inject_keycode(modifier, true);
+        }
+        inject_keycode(code, true);
+        inject_keycode(code, false);
+        for &modifier in modifiers.iter().rev() {
+            inject_keycode(modifier, false);
+        }
+    });
+    true
+}
+
+/// Types a character, pressing Shift if the US layout needs it.
+/// Returns false if there's no key for the character.
+///
+/// The keys are picked for the US layout, so with another layout set they decode
+/// to whatever those keys type there.
+pub fn inject_char(chr : char) -> bool {
+    match char_to_key(chr) {
+        Some((code, false)) => inject_combo(code, &[]),
+        Some((code, true)) => inject_combo(code, &[RawKeyCode::ShiftLeft]),
+        None => false
+    }
+}
+
+/// Presses & releases the key for a decoded key, characters are typed as `inject_char` does
+/// so they only come out as given with the US layout
+pub fn inject_key(key : DecodedKey) -> bool {
+    match key {
+        DecodedKey::Unicode(chr) => inject_char(chr),
+        DecodedKey::RawKey(code) => inject_combo(code, &[]),
+    }
+}
+
+/// Types a string, returning how many characters had a key on the US layout.
+/// See `inject_char` for other layouts.
+pub fn inject_str(text : &str) -> usize {
+    text.chars().filter(|&chr| inject_char(chr)).count()
+}
+
+/// The key & whether Shift is needed to type a character on the US layout
+pub fn char_to_key(chr : char) -> Option<(RawKeyCode, bool)> {
+    use RawKeyCode::*;
+    let shifted = chr.is_ascii_uppercase();
+    let key = match chr.to_ascii_lowercase() {
+        'a' => A, 'b' => B, 'c' => C, 'd' => D, 'e' => E, 'f' => F, 'g' => G,
+        'h' => H, 'i' => I, 'j' => J, 'k' => K, 'l' => L, 'm' => M, 'n' => N,
+        'o' => O, 'p' => P, 'q' => Q, 'r' => R, 's' => S, 't' => T, 'u' => U,
+        'v' => V, 'w' => W, 'x' => X, 'y' => Y, 'z' => Z,
+        '1' => Key1, '2' => Key2, '3' => Key3, '4' => Key4, '5' => Key5,
+        '6' => Key6, '7' => Key7, '8' => Key8, '9' => Key9, '0' => Key0,
+        '-' => Minus, '=' => Equals, '[' => BracketSquareLeft, ']' => BracketSquareRight,
+        '\\' => BackSlash, ';' => SemiColon, '\'' => Quote, '`' => BackTick,
+        ',' => Comma, '.' => Fullstop, '/' => Slash, ' ' => Spacebar,
+        '\n' | '\r' => Enter, '\t' => Tab, '\x08' => Backspace, '\x1B' => Escape, '\x7F' => Delete,
+        '!' => return Some((Key1, true)), '@' => return Some((Key2, true)),
+        '#' => return Some((Key3, true)), '$' => return Some((Key4, true)),
+        '%' => return Some((Key5, true)), '^' => return Some((Key6, true)),
+        '&' => return Some((Key7, true)), '*' => return Some((Key8, true)),
+        '(' => return Some((Key9, true)), ')' => return Some((Key0, true)),
+        '_' => return Some((Minus, true)), '+' => return Some((Equals, true)),
+        '{' => return Some((BracketSquareLeft, true)), '}' => return Some((BracketSquareRight, true)),
+        '|' => return Some((BackSlash, true)), ':' => return Some((SemiColon, true)),
+        '"' => return Some((Quote, true)), '~' => return Some((BackTick, true)),
+        '<' => return Some((Comma, true)), '>' => return Some((Fullstop, true)),
+        '?' => return Some((Slash, true)),
+        _ => return None
+    };
+    Some((key, shifted))
+}
+
+/// Whether a key is extended (E0 prefixed), and its make codes in sets 1 & 2
+fn scancodes(code : RawKeyCode) -> Option<(bool, u8, u8)> {
+    use RawKeyCode::*;
+    let codes = match code {
+        Escape => (false, 0x01, 0x76),
+        Key1 => (false, 0x02, 0x16), Key2 => (false, 0x03, 0x1E), Key3 => (false, 0x04, 0x26),
+        Key4 => (false, 0x05, 0x25), Key5 => (false, 0x06, 0x2E), Key6 => (false, 0x07, 0x36),
+        Key7 => (false, 0x08, 0x3D), Key8 => (false, 0x09, 0x3E), Key9 => (false, 0x0A, 0x46),
+        Key0 => (false, 0x0B, 0x45),
+        Minus => (false, 0x0C, 0x4E), Equals => (false, 0x0D, 0x55),
+        Backspace => (false, 0x0E, 0x66), Tab => (false, 0x0F, 0x0D),
+        Q => (false, 0x10, 0x15), W => (false, 0x11, 0x1D), E => (false, 0x12, 0x24),
+        R => (false, 0x13, 0x2D), T => (false, 0x14, 0x2C), Y => (false, 0x15, 0x35),
+        U => (false, 0x16, 0x3C), I => (false, 0x17, 0x43), O => (false, 0x18, 0x44),
+        P => (false, 0x19, 0x4D),
+        BracketSquareLeft => (false, 0x1A, 0x54), BracketSquareRight => (false, 0x1B, 0x5B),
+        Enter => (false, 0x1C, 0x5A), ControlLeft => (false, 0x1D, 0x14),
+        A => (false, 0x1E, 0x1C), S => (false, 0x1F, 0x1B), D => (false, 0x20, 0x23),
+        F => (false, 0x21, 0x2B), G => (false, 0x22, 0x34), H => (false, 0x23, 0x33),
+        J => (false, 0x24, 0x3B), K => (false, 0x25, 0x42), L => (false, 0x26, 0x4B),
+        SemiColon => (false, 0x27, 0x4C), Quote => (false, 0x28, 0x52), BackTick => (false, 0x29, 0x0E),
+        ShiftLeft => (false, 0x2A, 0x12), BackSlash => (false, 0x2B, 0x5D),
+        Z => (false, 0x2C, 0x1A), X => (false, 0x2D, 0x22), C => (false, 0x2E, 0x21),
+        V => (false, 0x2F, 0x2A), B => (false, 0x30, 0x32), N => (false, 0x31, 0x31),
+        M => (false, 0x32, 0x3A),
+        Comma => (false, 0x33, 0x41), Fullstop => (false, 0x34, 0x49), Slash => (false, 0x35, 0x4A),
+        ShiftRight => (false, 0x36, 0x59), AltLeft => (false, 0x38, 0x11),
+        Spacebar => (false, 0x39, 0x29), CapsLock => (false, 0x3A, 0x58),
+        F1 => (false, 0x3B, 0x05), F2 => (false, 0x3C, 0x06), F3 => (false, 0x3D, 0x04),
+        F4 => (false, 0x3E, 0x0C), F5 => (false, 0x3F, 0x03), F6 => (false, 0x40, 0x0B),
+        F7 => (false, 0x41, 0x83), F8 => (false, 0x42, 0x0A), F9 => (false, 0x43, 0x01),
+        F10 => (false, 0x44, 0x09), F11 => (false, 0x57, 0x78), F12 => (false, 0x58, 0x07),
+        ControlRight => (true, 0x1D, 0x14), AltRight => (true, 0x38, 0x11),
+        NumpadEnter => (true, 0x1C, 0x5A),
+        Home => (true, 0x47, 0x6C), ArrowUp => (true, 0x48, 0x75), PageUp => (true, 0x49, 0x7D),
+        ArrowLeft => (true, 0x4B, 0x6B), ArrowRight => (true, 0x4D, 0x74),
+        End => (true, 0x4F, 0x69), ArrowDown => (true, 0x50, 0x72), PageDown => (true, 0x51, 0x7A),
+        Insert => (true, 0x52, 0x70), Delete => (true, 0x53, 0x71),
+        _ => return None
+    };
+    Some(codes)
+}
@@ -0,0 +1,143 @@
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+
+use pc_keyboard::{DecodedKey, Error, HandleControl, KeyEvent, Keyboard, ScancodeSet, ScancodeSet1, ScancodeSet2, layouts};
+
+use super::ScancodeSetId;
+
+/// The keyboard layouts that can be selected at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Layout {
+    Us104     = 0,
+    Uk105     = 1,
+    De105     = 2,
+    Dvorak104 = 3,
+    Azerty    = 4,
+}
+
+/// Returned when a layout name isn't recognised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownLayout;
+
+impl Layout {
+    pub const ALL : [Layout; 5] = [Layout::Us104, Layout::Uk105, Layout::De105, Layout::Dvorak104, Layout::Azerty];
+
+    /// The short name used by `from_name`, e.g. `"uk"`
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Us104     => "us",
+            Layout::Uk105     => "uk",
+            Layout::De105     => "de",
+            Layout::Dvorak104 => "dvorak",
+            Layout::Azerty    => "azerty",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Layout::Us104     => "US 104-key",
+            Layout::Uk105     => "UK 105-key",
+            Layout::De105     => "German 105-key",
+            Layout::Dvorak104 => "Dvorak 104-key",
+            Layout::Azerty    => "French AZERTY",
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Layout> {
+        let name = name.trim();
+        Layout::ALL.iter().copied().find(|layout| layout.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_u8(value : u8) -> Option<Layout> {
+        Layout::ALL.get(value as usize).copied()
+    }
+}
+
+impl FromStr for Layout {
+    type Err = UnknownLayout;
+
+    fn from_str(s : &str) -> Result<Layout, UnknownLayout> {
+        Layout::from_name(s).ok_or(UnknownLayout)
+    }
+}
+
+impl Display for Layout {
+    fn fmt(&self, f : &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A `pc_keyboard` decoder for whichever layout is selected
+pub(crate) enum LayoutKeyboard<S : ScancodeSet> {
+    Us104(Keyboard<layouts::Us104Key, S>),
+    Uk105(Keyboard<layouts::Uk105Key, S>),
+    De105(Keyboard<layouts::De105Key, S>),
+    Dvorak104(Keyboard<layouts::Dvorak104Key, S>),
+    Azerty(Keyboard<layouts::Azerty, S>),
+}
+
+macro_rules! with_keyboard {
+    ($keyboard:expr, $k:ident => $body:expr) => {
+        match $keyboard {
+            LayoutKeyboard::Us104($k)     => $body,
+            LayoutKeyboard::Uk105($k)     => $body,
+            LayoutKeyboard::De105($k)     => $body,
+            LayoutKeyboard::Dvorak104($k) => $body,
+            LayoutKeyboard::Azerty($k)    => $body,
+        }
+    };
+}
+
+impl<S : ScancodeSet> LayoutKeyboard<S> {
+    pub(crate) fn new(layout : Layout, set : S, handle_ctrl : HandleControl) -> LayoutKeyboard<S> {
+        match layout {
+            Layout::Us104     => LayoutKeyboard::Us104(Keyboard::new(layouts::Us104Key, set, handle_ctrl)),
+            Layout::Uk105     => LayoutKeyboard::Uk105(Keyboard::new(layouts::Uk105Key, set, handle_ctrl)),
+            Layout::De105     => LayoutKeyboard::De105(Keyboard::new(layouts::De105Key, set, handle_ctrl)),
+            Layout::Dvorak104 => LayoutKeyboard::Dvorak104(Keyboard::new(layouts::Dvorak104Key, set, handle_ctrl)),
+            Layout::Azerty    => LayoutKeyboard::Azerty(Keyboard::new(layouts::Azerty, set, handle_ctrl)),
+        }
+    }
+
+    pub(crate) fn add_byte(&mut self, byte : u8) -> Result<Option<KeyEvent>, Error> {
+        with_keyboard!(self, k => k.add_byte(byte))
+    }
+
+    pub(crate) fn process_keyevent(&mut self, event : KeyEvent) -> Option<DecodedKey> {
+        with_keyboard!(self, k => k.process_keyevent(event))
+    }
+}
+
+/// A layout decoder for whichever scancode set the keyboard sends
+pub(crate) enum SetKeyboard {
+    Set1(LayoutKeyboard<ScancodeSet1>),
+    Set2(LayoutKeyboard<ScancodeSet2>),
+}
+
+impl SetKeyboard {
+    pub(crate) fn new(layout : Layout, set : ScancodeSetId, handle_ctrl : HandleControl) -> SetKeyboard {
+        match set {
+            ScancodeSetId::Set1 => SetKeyboard::Set1(LayoutKeyboard::new(layout, ScancodeSet1, handle_ctrl)),
+            ScancodeSetId::Set2 => SetKeyboard::Set2(LayoutKeyboard::new(layout, ScancodeSet2, handle_ctrl)),
+        }
+    }
+
+    pub(crate) fn add_byte(&mut self, byte : u8) -> Result<Option<KeyEvent>, Error> {
+        match self {
+            SetKeyboard::Set1(k) => k.add_byte(byte),
+            SetKeyboard::Set2(k) => k.add_byte(byte),
+        }
+    }
+
+    pub(crate) fn process_keyevent(&mut self, event : KeyEvent) -> Option<DecodedKey> {
+        match self {
+            SetKeyboard::Set1(k) => k.process_keyevent(event),
+            SetKeyboard::Set2(k) => k.process_keyevent(event),
+        }
+    }
+}
@@ -0,0 +1,1081 @@
+use alloc::vec::Vec;
+use x86_64::instructions::interrupts::without_interrupts;
+use core::sync::atomic::{AtomicU8, AtomicU32, AtomicUsize, Ordering};
+use conquer_once::spin::OnceCell;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use crossbeam_queue::ArrayQueue;
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::task::AtomicWaker;
+use pc_keyboard::{DecodedKey, HandleControl};
+use spin::Mutex;
+use tinix_fs::api::{FileReader, FileInteractor, File};
+
+use crate::devices::nvram::{self, NvramError};
+use crate::devices::ps2::{self, Ps2Error};
+use crate::io::vconsole;
+
+pub mod layout;
+pub mod hotkey;
+pub mod inject;
+pub mod replay;
+
+pub use layout::Layout;
+pub use hotkey::{register_hotkey, unregister_hotkey, Hotkey, HotkeyError};
+pub use inject::{inject_scancode, inject_key, inject_str};
+use layout::SetKeyboard;
+
+
+
+/// Number of scancodes buffered between the keyboard interrupt and its readers
+const SCANCODE_QUEUE_SIZE : usize = 128;
+
+/// The scancodes typed on one virtual console, only the active console's queue is filled
+struct InputQueue {
+    scancodes : ArrayQueue<u8>,
+    /// Keys that were decoded elsewhere, see `push_key`
+    events    : ArrayQueue<KeyEvent>,
+    waker     : AtomicWaker,
+    /// Each console decodes its own scancodes, so a key held on one doesn't leak into another
+    input     : Mutex<ConsoleInput>,
+}
+
+struct ConsoleInput {
+    keyboard : SetKeyboard,
+    state    : InputState,
+}
+
+/// One queue per virtual console
+static INPUT_QUEUES : OnceCell<Vec<InputQueue>> = OnceCell::uninit();
+
+/// Scancodes & keys dropped because the queue was full or not yet initialised
+static DROPPED_SCANCODES : AtomicUsize = AtomicUsize::new(0);
+
+/// Allocates each console's scancode queue & decoder, needs the heap. Until this is called, scancodes are dropped.
+pub fn init() {
+    without_interrupts(|| {
+        let settings = KEYBOARD.lock();
+        let _ = INPUT_QUEUES.try_init_once(|| {
+            (0..vconsole::CONSOLE_COUNT).map(|_| InputQueue {
+                scancodes : ArrayQueue::new(SCANCODE_QUEUE_SIZE),
+                events    : ArrayQueue::new(SCANCODE_QUEUE_SIZE),
+                waker     : AtomicWaker::new(),
+                input     : Mutex::new(ConsoleInput {
+                    keyboard : settings.keyboard(),
+                    state    : InputState::new(),
+                }),
+            }).collect()
+        });
+    });
+}
+
+fn input_queue(console : usize) -> Option<&'static InputQueue> {
+    INPUT_QUEUES.try_get().ok()?.get(console)
+}
+
+/// Number of scancodes dropped since boot because nobody was reading them fast enough
+pub fn dropped_scancodes() -> usize {
+    DROPPED_SCANCODES.load(Ordering::Relaxed)
+}
+
+/// None until `init` has been called, so reading never allocates
+fn get_scancode(console : usize) -> Option<u8> {
+    input_queue(console)?.scancodes.pop().ok()
+}
+
+/// Called from the keyboard interrupt, must not allocate or block
+pub(crate) fn add_scancode(scancode : u8) {
+    hotkey::filter_scancode(scancode, &mut push_scancode);
+}
+
+/// Queues releases for the modifiers held down when a console is switched away from,
+/// so they aren't left stuck down on it. Called from the keyboard interrupt.
+pub(crate) fn release_modifiers(console : usize) {
+    if let Some(queue) = input_queue(console) {
+        hotkey::release_held_modifiers(&mut |scancode| {
+            if queue.scancodes.push(scancode).is_err() {
+                DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        queue.waker.wake();
+    }
+}
+
+fn push_scancode(scancode : u8) {
+    match input_queue(vconsole::active()) {
+        Some(queue) => {
+            if queue.scancodes.push(scancode).is_err() {
+                DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+            } else {
+                queue.waker.wake();
+            }
+        }
+        None => {
+            DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Queues a press & release of a key that's already decoded for the active console. This skips the
+/// scancode decoder, the layout & hotkeys, for input that doesn't come from a keyboard such as
+/// a terminal on the serial port. Called from interrupts, must not allocate or block.
+pub(crate) fn push_key(key : DecodedKey, modifiers : Modifiers) {
+    let (code, raw, chr) = match key {
+        DecodedKey::RawKey(raw) => (KeyCode::from_pc_keycode(raw), raw, None),
+        DecodedKey::Unicode(chr) => {
+            // Ctrl+letter comes out the way the keyboard's own decoder would give it
+            let ignore_ctrl = match KEYBOARD.try_lock().map(|settings| settings.handle_ctrl) {
+                Some(HandleControl::Ignore) => true,
+                _ => false
+            };
+            let chr = match chr {
+                '\u{1}'..='\u{1A}' if modifiers.ctrl() && ignore_ctrl => (chr as u8 - 1 + b'a') as char,
+                _ => chr
+            };
+            (KeyCode::from_char(chr), raw_key_for(chr), Some(chr))
+        }
+    };
+
+    let queue = match input_queue(vconsole::active()) {
+        Some(queue) => queue,
+        None => {
+            DROPPED_SCANCODES.fetch_add(2, Ordering::Relaxed);
+            return;
+        }
+    };
+    for &(state, chr) in &[(KeyState::Down, chr), (KeyState::Up, None)] {
+        if queue.events.push(KeyEvent { code, raw, state, modifiers, chr }).is_err() {
+            DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    queue.waker.wake();
+}
+
+/// The US layout key a character is typed with, the physical key isn't known for pushed keys
+fn raw_key_for(chr : char) -> pc_keyboard::KeyCode {
+    let chr = match chr {
+        '\t' | '\n' | '\r' | '\x08' => chr,
+        '\u{1}'..='\u{1A}' => (chr as u8 - 1 + b'a') as char,
+        _ => chr
+    };
+    inject::char_to_key(chr).map_or(pc_keyboard::KeyCode::Spacebar, |(raw, _)| raw)
+}
+
+/// The next key queued by `push_key` on a console
+fn take_pushed_key(console : usize) -> Option<KeyEvent> {
+    let event = input_queue(console)?.events.pop().ok()?;
+    scroll_on_key(&event);
+    Some(event)
+}
+
+/// An asynchronous stream of raw scancodes, woken by the keyboard interrupt
+pub struct ScancodeStream {
+    console : usize
+}
+
+impl ScancodeStream {
+    /// The scancodes typed on the first virtual console
+    pub fn new() -> ScancodeStream {
+        ScancodeStream::for_console(0)
+    }
+
+    /// The scancodes typed while a virtual console is active
+    pub fn for_console(console : usize) -> ScancodeStream {
+        init();
+        assert!(console < vconsole::CONSOLE_COUNT, "No Such Virtual Console");
+        ScancodeStream { console }
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self : Pin<&mut Self>, cx : &mut Context) -> Poll<Option<u8>> {
+        let queue = input_queue(self.console).expect("Scancode Queue Not Initialized");
+
+        if let Ok(scancode) = queue.scancodes.pop() {
+            return Poll::Ready(Some(scancode));
+        }
+
+        queue.waker.register(&cx.waker());
+        match queue.scancodes.pop() {
+            Ok(scancode) => {
+                queue.waker.take();
+                Poll::Ready(Some(scancode))
+            }
+            Err(_) => Poll::Pending,
+        }
+    }
+}
+
+/// An asynchronous stream of decoded keys
+pub struct KeyStream {
+    events : KeyEventStream
+}
+
+impl KeyStream {
+    pub fn new() -> KeyStream {
+        KeyStream { events : KeyEventStream::new() }
+    }
+}
+
+impl Stream for KeyStream {
+    type Item = DecodedKey;
+
+    fn poll_next(mut self : Pin<&mut Self>, cx : &mut Context) -> Poll<Option<DecodedKey>> {
+        loop {
+            match Pin::new(&mut self.events).poll_next(cx) {
+                Poll::Ready(Some(event)) => {
+                    if let Some(key) = event.decoded() {
+                        return Poll::Ready(Some(key));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Waits for the next decoded key without spinning
+pub async fn next_key() -> DecodedKey {
+    let mut keys = KeyStream::new();
+    loop {
+        if let Some(key) = keys.next().await {
+            return key;
+        }
+    }
+}
+
+/// An asynchronous stream of key presses, repeats & releases
+pub struct KeyEventStream {
+    scancodes : ScancodeStream
+}
+
+impl KeyEventStream {
+    pub fn new() -> KeyEventStream {
+        KeyEventStream { scancodes : ScancodeStream::new() }
+    }
+
+    /// The key events typed while a virtual console is active
+    pub fn for_console(console : usize) -> KeyEventStream {
+        KeyEventStream { scancodes : ScancodeStream::for_console(console) }
+    }
+}
+
+impl Stream for KeyEventStream {
+    type Item = KeyEvent;
+
+    fn poll_next(mut self : Pin<&mut Self>, cx : &mut Context) -> Poll<Option<KeyEvent>> {
+        let console = self.scancodes.console;
+        loop {
+            if let Some(event) = take_pushed_key(console) {
+                return Poll::Ready(Some(event));
+            }
+            match Pin::new(&mut self.scancodes).poll_next(cx) {
+                Poll::Ready(Some(scancode)) => {
+                    if let Some(event) = process_scancode(console, scancode) {
+                        return Poll::Ready(Some(event));
+                    }
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                // The waker is registered by now, so a key pushed after this is woken for
+                Poll::Pending => return match take_pushed_key(console) {
+                    Some(event) => Poll::Ready(Some(event)),
+                    None => Poll::Pending
+                },
+            }
+        }
+    }
+}
+
+/// The last character returned by `StandardIn`, used to detect the end of a line or file
+static LAST_CHAR : AtomicU32 = AtomicU32::new(0);
+
+/// Returns the next key typed, with layout & modifiers applied, skipping key releases
+pub fn get_decoded_key() -> Option<DecodedKey> {
+    loop {
+        if let Some(key) = get_key_event()?.decoded() {
+            return Some(key);
+        }
+    }
+}
+
+/// Returns the next character typed, skipping keys that don't produce one
+pub fn get_ascii_key() -> Option<char> {
+    loop {
+        let event = get_key_event()?;
+        if event.state != KeyState::Up {
+            if let Some(chr) = event.chr {
+                return Some(chr);
+            }
+        }
+    }
+}
+
+pub fn get_keycode() -> Option<KeyCode> {
+    Some(KeyCode::from_dec_key(get_decoded_key()?))
+}
+
+/// Returns the next press, repeat or release of any key, including modifiers,
+/// typed on the first virtual console
+pub fn get_key_event() -> Option<KeyEvent> {
+    get_key_event_from(0)
+}
+
+/// Returns the next key event typed while a virtual console was active
+pub fn get_key_event_from(console : usize) -> Option<KeyEvent> {
+    if let Some(event) = take_pushed_key(console) {
+        return Some(event);
+    }
+    loop {
+        if let Some(event) = process_scancode(console, get_scancode(console)?) {
+            return Some(event);
+        }
+    }
+}
+
+/// The current state of the modifier & lock keys on the active console
+pub fn modifiers() -> Modifiers {
+    match input_queue(vconsole::active()) {
+        Some(queue) => without_interrupts(|| queue.input.lock().state.modifiers),
+        None => Modifiers::default()
+    }
+}
+
+/// The lock LEDs last set, one bit each for Scroll, Num & Caps Lock
+static LEDS : AtomicU8 = AtomicU8::new(0);
+
+fn led_bits(m : &Modifiers) -> u8 {
+    (m.scroll_lock as u8) | (m.num_lock as u8) << 1 | (m.caps_lock as u8) << 2
+}
+
+/// Sets the keyboard's lock LEDs to match the active console's lock state
+pub fn sync_leds() {
+    if crate::devices::ps2::keyboard_present() {
+        let m = modifiers();
+        LEDS.store(led_bits(&m), Ordering::Relaxed);
+        let _ = crate::devices::ps2::set_leds(m.scroll_lock, m.num_lock, m.caps_lock);
+    }
+}
+
+/// Whether a key was pressed, held down or released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyState {
+    Down,
+    Up,
+    /// The key was held down long enough for the keyboard to repeat it
+    Repeat,
+}
+
+/// The state of the modifier & lock keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub lshift      : bool,
+    pub rshift      : bool,
+    pub lctrl       : bool,
+    pub rctrl       : bool,
+    pub lalt        : bool,
+    pub ralt        : bool,
+    pub lmeta       : bool,
+    pub rmeta       : bool,
+    pub caps_lock   : bool,
+    pub num_lock    : bool,
+    pub scroll_lock : bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool { self.lshift || self.rshift }
+    pub fn ctrl(&self) -> bool { self.lctrl || self.rctrl }
+    pub fn alt(&self) -> bool { self.lalt || self.ralt }
+    pub fn meta(&self) -> bool { self.lmeta || self.rmeta }
+}
+
+/// A key being pressed, repeated or released
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The physical key, independent of the layout
+    pub code      : KeyCode,
+    pub raw       : pc_keyboard::KeyCode,
+    pub state     : KeyState,
+    /// Modifiers after this event was applied
+    pub modifiers : Modifiers,
+    /// The character typed, as mapped by the layout, for presses & repeats
+    pub chr       : Option<char>,
+}
+
+impl KeyEvent {
+    pub fn is_down(&self) -> bool {
+        self.state != KeyState::Up
+    }
+
+    /// The equivalent `pc_keyboard` decoded key, `None` for releases
+    pub fn decoded(&self) -> Option<DecodedKey> {
+        match (self.state, self.chr) {
+            (KeyState::Up, _)     => None,
+            (_, Some(chr))        => Some(DecodedKey::Unicode(chr)),
+            (_, None)             => Some(DecodedKey::RawKey(self.raw)),
+        }
+    }
+}
+
+struct InputState {
+    modifiers : Modifiers,
+    /// One bit per `pc_keyboard::KeyCode`, set while the key is held down
+    pressed   : [u64; 4],
+}
+
+impl InputState {
+    const fn new() -> InputState {
+        InputState {
+            modifiers : Modifiers {
+                lshift : false, rshift : false, lctrl : false, rctrl : false,
+                lalt : false, ralt : false, lmeta : false, rmeta : false,
+                caps_lock : false, num_lock : false, scroll_lock : false,
+            },
+            pressed : [0; 4],
+        }
+    }
+
+    /// Applies a key event, returning whether it's a press, repeat or release
+    fn update(&mut self, code : pc_keyboard::KeyCode, down : bool) -> KeyState {
+        let index = code as usize;
+        let (word, bit) = (index / 64 % 4, 1u64 << (index % 64));
+        let was_down = self.pressed[word] & bit != 0;
+
+        if down { self.pressed[word] |= bit } else { self.pressed[word] &= !bit }
+
+        let state = match (down, was_down) {
+            (false, _)    => KeyState::Up,
+            (true, true)  => KeyState::Repeat,
+            (true, false) => KeyState::Down,
+        };
+
+        let m = &mut self.modifiers;
+        match code {
+            pc_keyboard::KeyCode::ShiftLeft    => m.lshift = down,
+            pc_keyboard::KeyCode::ShiftRight   => m.rshift = down,
+            pc_keyboard::KeyCode::ControlLeft  => m.lctrl = down,
+            pc_keyboard::KeyCode::ControlRight => m.rctrl = down,
+            pc_keyboard::KeyCode::AltLeft      => m.lalt = down,
+            pc_keyboard::KeyCode::AltRight     => m.ralt = down,
+            pc_keyboard::KeyCode::WindowsLeft  => m.lmeta = down,
+            pc_keyboard::KeyCode::WindowsRight => m.rmeta = down,
+            pc_keyboard::KeyCode::CapsLock   if state == KeyState::Down => m.caps_lock = !m.caps_lock,
+            pc_keyboard::KeyCode::NumpadLock if state == KeyState::Down => m.num_lock = !m.num_lock,
+            pc_keyboard::KeyCode::ScrollLock if state == KeyState::Down => m.scroll_lock = !m.scroll_lock,
+            _ => {}
+        }
+
+        state
+    }
+}
+
+/// Feeds a scancode through a console's decoder, returning an event once a key is complete
+fn process_scancode(console : usize, scancode : u8) -> Option<KeyEvent> {
+    let event = decode_event(console, scancode)?;
+    // Each console has its own lock state, the LEDs follow the active one
+    if console == vconsole::active() && led_bits(&event.modifiers) != LEDS.load(Ordering::Relaxed) {
+        sync_leds();
+    }
+    scroll_on_key(&event);
+    Some(event)
+}
+
+/// Typing brings a scrolled back terminal back to the live output
+fn scroll_on_key(event : &KeyEvent) {
+    if event.state == KeyState::Down && !event.code.is_modifier() {
+        vconsole::scroll_to_bottom();
+    }
+}
+
+fn decode_event(console : usize, scancode : u8) -> Option<KeyEvent> {
+    let queue = input_queue(console)?;
+    without_interrupts(|| {
+        let mut input = queue.input.lock();
+        let input = &mut *input;
+        let event = input.keyboard.add_byte(scancode).ok()??;
+        let raw = event.code;
+        let down = event.state == pc_keyboard::KeyState::Down;
+        let decoded = input.keyboard.process_keyevent(event);
+
+        let state = input.state.update(raw, down);
+        let chr = match decoded {
+            Some(DecodedKey::Unicode(chr)) if down => Some(chr),
+            _ => None
+        };
+
+        Some(KeyEvent {
+            code      : KeyCode::from_pc_keycode(raw),
+            raw,
+            state,
+            modifiers : input.state.modifiers,
+            chr,
+        })
+    })
+}
+
+/// How scancodes are decoded, every console's decoder is built from these
+struct DecoderSettings {
+    layout      : Layout,
+    set         : ScancodeSetId,
+    handle_ctrl : HandleControl,
+}
+
+impl DecoderSettings {
+    fn keyboard(&self) -> SetKeyboard {
+        SetKeyboard::new(self.layout, self.set, self.handle_ctrl)
+    }
+
+    /// Rebuilds every console's decoder, keys already being decoded are discarded
+    fn apply(&self) {
+        if let Ok(queues) = INPUT_QUEUES.try_get() {
+            for queue in queues.iter() {
+                queue.input.lock().keyboard = self.keyboard();
+            }
+        }
+    }
+}
+
+static KEYBOARD : Mutex<DecoderSettings> = Mutex::new(DecoderSettings {
+    layout      : Layout::Us104,
+    set         : ScancodeSetId::Set1,
+    handle_ctrl : HandleControl::Ignore,
+});
+
+/// Switches the keyboard layout, keys already being decoded are discarded
+pub fn set_layout(layout : Layout) {
+    without_interrupts(|| {
+        let mut settings = KEYBOARD.lock();
+        settings.layout = layout;
+        settings.apply();
+    });
+}
+
+pub fn layout() -> Layout {
+    without_interrupts(|| KEYBOARD.lock().layout)
+}
+
+/// Sets how Ctrl+letter is decoded, `HandleControl::MapLettersToUnicode` turns Ctrl+C into '\x03'
+pub fn set_handle_control(handle_ctrl : HandleControl) {
+    without_interrupts(|| {
+        let mut settings = KEYBOARD.lock();
+        settings.handle_ctrl = handle_ctrl;
+        settings.apply();
+    });
+}
+
+/// The scancode sets that can be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSetId {
+    Set1,
+    Set2,
+}
+
+/// The scancode set being decoded
+pub fn scancode_set() -> ScancodeSetId {
+    without_interrupts(|| KEYBOARD.lock().set)
+}
+
+/// Decodes another scancode set, keys already being decoded are discarded
+pub fn set_scancode_set(set : ScancodeSetId) {
+    without_interrupts(|| {
+        let mut settings = KEYBOARD.lock();
+        settings.set = set;
+        settings.apply();
+    });
+    hotkey::set_scancode_set(set);
+}
+
+/// Works out which scancode set arrives from the controller & decodes it.
+///
+/// With translation on that's always set 1, so the keyboard is put into set 2 if it isn't already.
+/// Without it, keyboards in set 3 are switched to set 2.
+pub fn detect_scancode_set() -> ScancodeSetId {
+    if !ps2::keyboard_present() {
+        return scancode_set();
+    }
+
+    let reported = ps2::keyboard_scancode_set();
+    let set = if ps2::translation_enabled() {
+        if reported != Ok(2) {
+            let _ = ps2::set_keyboard_scancode_set(2);
+        }
+        ScancodeSetId::Set1
+    } else {
+        match reported {
+            Ok(1) => ScancodeSetId::Set1,
+            Ok(2) => ScancodeSetId::Set2,
+            _ if ps2::set_keyboard_scancode_set(2).is_ok() => ScancodeSetId::Set2,
+            _ => scancode_set(),
+        }
+    };
+    set_scancode_set(set);
+    set
+}
+
+/// Turns the controller's translation to set 1 on or off, then decodes whichever set arrives
+pub fn set_translation(enabled : bool) -> Result<ScancodeSetId, Ps2Error> {
+    ps2::set_translation(enabled)?;
+    Ok(detect_scancode_set())
+}
+
+/// Stores the current layout in NVRAM, `init` restores it on the next boot
+pub fn save_layout() -> Result<(), NvramError> {
+    nvram::set_u8(nvram::KEY_KEYBOARD_LAYOUT, layout().as_u8())
+}
+
+/// Restores the layout stored by `save_layout`, returning false if none was stored
+pub fn load_layout() -> bool {
+    match nvram::get_u8(nvram::KEY_KEYBOARD_LAYOUT).and_then(Layout::from_u8) {
+        Some(layout) => { set_layout(layout); true }
+        None => false
+    }
+}
+
+/// Handles a `layout` shell command: with no argument, lists the available layouts
+/// and marks the current one, otherwise switches to & saves the named layout
+pub fn layout_command(arg : &str) {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        let current = layout();
+        for layout in Layout::ALL.iter() {
+            let marker = if *layout == current { '*' } else { ' ' };
+            crate::println!("{} {:<8}{}", marker, layout.name(), layout.description());
+        }
+        return;
+    }
+
+    match Layout::from_name(arg) {
+        Some(layout) => {
+            set_layout(layout);
+            if save_layout().is_err() {
+                crate::println!("Layout set to {}, but couldn't be saved", layout);
+            }
+        }
+        None => crate::println!("Unknown layout '{}'", arg),
+    }
+}
+
+
+pub struct StandardIn {_private : ()}
+
+impl StandardIn {
+    pub fn get() -> StandardIn { StandardIn {_private : ()} }
+}
+
+impl FileInteractor for StandardIn {
+    fn at_end(&self) -> bool {
+        let last = LAST_CHAR.load(Ordering::Relaxed);
+        last == 0x1A || last == '\n' as u32
+    }
+
+    fn close(_file : File) {
+        
+    }
+
+    fn get_pos(_file : File) -> usize {
+        0
+    }
+
+    fn get_handle(_file : File) -> usize {
+        1
+    }
+
+    fn is_eof(_file : File) -> bool {
+        LAST_CHAR.load(Ordering::Relaxed) == 0x04
+    }
+
+    fn open(_path : &str) -> File {
+        File::new(1,1)
+    }
+
+    fn set_pos(_pos  : usize, _file : File) {
+        
+    }
+}
+
+impl FileReader<char> for StandardIn {
+    fn open(_file : &File) -> StandardIn {
+        StandardIn {_private : ()}
+    }
+
+    fn read(&mut self) -> Option<char> {
+        let chr = get_ascii_key()?;
+        LAST_CHAR.store(chr as u32, Ordering::Relaxed);
+        Some(chr)
+    }
+}
+
+
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[allow(non_camel_case_types)]
+pub enum KeyCode {
+    NUL = 0,
+    SOH,
+    STX,
+    ETX,
+    EOT,
+    ENQ,
+    ACK,
+    BEL,
+    BS,
+    HT,
+    LF,
+    VT,
+    FF,
+    CR,
+    SO,
+    SI,
+    DLE,
+    DC1,
+    DC2,
+    DC3,
+    DC4,
+    NAK,
+    SYN,
+    ETB,
+    CAN,
+    EM,
+    SUB,
+    ESC,
+    FS,
+    GS,
+    RS,
+    US,
+    KEY_SPACE,
+    KEY_BANG,
+    KEY_DQUOTE,
+    KEY_HASH,
+    KEY_DOLLAR,
+    KEY_PERCENT,
+    KEY_AMPERSAND,
+    KEY_SQUOTE,
+    KEY_RIGHT_BRACKET,
+    KEY_LEFT_BRACKET,
+    KEY_STAR,
+    KEY_PLUS,
+    KEY_COMMA,
+    KEY_MINUS,
+    KEY_DOT,
+    KEY_FSLASH,
+    KEY_0,
+    KEY_1,
+    KEY_2,
+    KEY_3,
+    KEY_4,
+    KEY_5,
+    KEY_6,
+    KEY_7,
+    KEY_8,
+    KEY_9,
+    KEY_COLON,
+    KEY_SEMI_COLON,
+    KEY_RIGHT_ARROW,
+    KEY_EQUAL,
+    KEY_LEFT_ARROW,
+    KEY_QUESTION,
+    KEY_AT,
+    KEY_A,
+    KEY_B,
+    KEY_C,
+    KEY_D,
+    KEY_E,
+    KEY_F,
+    KEY_G,
+    KEY_H,
+    KEY_I,
+    KEY_J,
+    KEY_K,
+    KEY_L,
+    KEY_M,
+    KEY_N,
+    KEY_O,
+    KEY_P,
+    KEY_Q,
+    KEY_R,
+    KEY_S,
+    KEY_T,
+    KEY_U,
+    KEY_V,
+    KEY_W,
+    KEY_X,
+    KEY_Y,
+    KEY_Z,
+    KEY_RIGHT_SQUARE_BRACKET,
+    KEY_BSLASH,
+    KEY_LEFT_SQUARE_BRACKET,
+    KEY_CARET,
+    KEY_UNDERSCORE,
+    KEY_BACKTICK,
+    KEY_RIGHT_CURLY_BRACKET = 123,
+    KEY_PIPE,
+    KEY_LEFT_CURLY_BRACKET,
+    KEY_TILDE,
+    DEL,
+
+    ARROW_UP, ARROW_DOWN, ARROW_LEFT, ARROW_RIGHT,
+
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+
+    HOME, END, PAGE_UP, PAGE_DOWN, INSERT,
+
+    KP_0, KP_1, KP_2, KP_3, KP_4, KP_5, KP_6, KP_7, KP_8, KP_9,
+    KP_DOT, KP_ENTER, KP_PLUS, KP_MINUS, KP_STAR, KP_SLASH,
+
+    SHIFT_LEFT, SHIFT_RIGHT, CTRL_LEFT, CTRL_RIGHT, ALT_LEFT, ALT_RIGHT, META_LEFT, META_RIGHT, MENU,
+    CAPS_LOCK, NUM_LOCK, SCROLL_LOCK,
+
+    PRINT_SCREEN, PAUSE
+}
+
+impl KeyCode {
+    pub fn from_u8(x : usize) -> KeyCode {
+        match x {
+            0x00 => Self::NUL,
+            0x01 => Self::SOH,
+            0x02 => Self::STX,
+            0x03 => Self::ETX,
+            0x04 => Self::EOT,
+            0x05 => Self::ENQ,
+            0x06 => Self::BS,
+
+            _ => Self::NUL
+        }
+    }
+
+    pub fn from_dec_key(k : DecodedKey) -> KeyCode {
+        match k {
+            DecodedKey::RawKey(c) => KeyCode::from_pc_keycode(c),
+            DecodedKey::Unicode(c) => KeyCode::from_char(c),
+        }
+    }
+
+    /// The key a character is typed with, letters map to their upper-case key
+    pub fn from_char(c : char) -> KeyCode {
+        match c {
+            'A' | 'a' => KeyCode::KEY_A,
+            'B' | 'b' => KeyCode::KEY_B,
+            'C' | 'c' => KeyCode::KEY_C,
+            'D' | 'd' => KeyCode::KEY_D,
+            'E' | 'e' => KeyCode::KEY_E,
+            'F' | 'f' => KeyCode::KEY_F,
+            'G' | 'g' => KeyCode::KEY_G,
+            'H' | 'h' => KeyCode::KEY_H,
+            'I' | 'i' => KeyCode::KEY_I,
+            'J' | 'j' => KeyCode::KEY_J,
+            'K' | 'k' => KeyCode::KEY_K,
+            'L' | 'l' => KeyCode::KEY_L,
+            'M' | 'm' => KeyCode::KEY_M,
+            'N' | 'n' => KeyCode::KEY_N,
+            'O' | 'o' => KeyCode::KEY_O,
+            'P' | 'p' => KeyCode::KEY_P,
+            'Q' | 'q' => KeyCode::KEY_Q,
+            'R' | 'r' => KeyCode::KEY_R,
+            'S' | 's' => KeyCode::KEY_S,
+            'T' | 't' => KeyCode::KEY_T,
+            'U' | 'u' => KeyCode::KEY_U,
+            'V' | 'v' => KeyCode::KEY_V,
+            'W' | 'w' => KeyCode::KEY_W,
+            'X' | 'x' => KeyCode::KEY_X,
+            'Y' | 'y' => KeyCode::KEY_Y,
+            'Z' | 'z' => KeyCode::KEY_Z,
+            '0' => KeyCode::KEY_0,
+            '1' => KeyCode::KEY_1,
+            '2' => KeyCode::KEY_2,
+            '3' => KeyCode::KEY_3,
+            '4' => KeyCode::KEY_4,
+            '5' => KeyCode::KEY_5,
+            '6' => KeyCode::KEY_6,
+            '7' => KeyCode::KEY_7,
+            '8' => KeyCode::KEY_8,
+            '9' => KeyCode::KEY_9,
+            '\x08' => KeyCode::BS,
+            '\t' => KeyCode::HT,
+            '\n' => KeyCode::LF,
+            '\r' => KeyCode::CR,
+            '\x1B' => KeyCode::ESC,
+            '\x7F' => KeyCode::DEL,
+            ' ' => KeyCode::KEY_SPACE,
+            '!' => KeyCode::KEY_BANG,
+            '"' => KeyCode::KEY_DQUOTE,
+            '#' => KeyCode::KEY_HASH,
+            '$' => KeyCode::KEY_DOLLAR,
+            '%' => KeyCode::KEY_PERCENT,
+            '&' => KeyCode::KEY_AMPERSAND,
+            '\'' => KeyCode::KEY_SQUOTE,
+            '(' => KeyCode::KEY_RIGHT_BRACKET,
+            ')' => KeyCode::KEY_LEFT_BRACKET,
+            '*' => KeyCode::KEY_STAR,
+            '+' => KeyCode::KEY_PLUS,
+            ',' => KeyCode::KEY_COMMA,
+            '-' => KeyCode::KEY_MINUS,
+            '.' => KeyCode::KEY_DOT,
+            '/' => KeyCode::KEY_FSLASH,
+            ':' => KeyCode::KEY_COLON,
+            ';' => KeyCode::KEY_SEMI_COLON,
+            '<' => KeyCode::KEY_RIGHT_ARROW,
+            '=' => KeyCode::KEY_EQUAL,
+            '>' => KeyCode::KEY_LEFT_ARROW,
+            '?' => KeyCode::KEY_QUESTION,
+            '@' => KeyCode::KEY_AT,
+            '[' => KeyCode::KEY_RIGHT_SQUARE_BRACKET,
+            '\\' => KeyCode::KEY_BSLASH,
+            ']' => KeyCode::KEY_LEFT_SQUARE_BRACKET,
+            '^' => KeyCode::KEY_CARET,
+            '_' => KeyCode::KEY_UNDERSCORE,
+            '`' => KeyCode::KEY_BACKTICK,
+            '{' => KeyCode::KEY_RIGHT_CURLY_BRACKET,
+            '|' => KeyCode::KEY_PIPE,
+            '}' => KeyCode::KEY_LEFT_CURLY_BRACKET,
+            '~' => KeyCode::KEY_TILDE,
+            c if (c as u32) < 0x20 => KeyCode::from_control_char(c as u8),
+            _ => KeyCode::NUL
+        }
+    }
+
+    /// The key for a control character produced with Ctrl, e.g. 0x03 (Ctrl+C) is KEY_C
+    fn from_control_char(c : u8) -> KeyCode {
+        KeyCode::from_char((c + b'@') as char)
+    }
+
+    /// The physical key, ignoring the keyboard layout & modifiers
+    pub fn from_pc_keycode(k : pc_keyboard::KeyCode) -> KeyCode {
+        match k {
+            pc_keyboard::KeyCode::A => KeyCode::KEY_A,
+            pc_keyboard::KeyCode::B => KeyCode::KEY_B,
+            pc_keyboard::KeyCode::C => KeyCode::KEY_C,
+            pc_keyboard::KeyCode::D => KeyCode::KEY_D,
+            pc_keyboard::KeyCode::E => KeyCode::KEY_E,
+            pc_keyboard::KeyCode::F => KeyCode::KEY_F,
+            pc_keyboard::KeyCode::G => KeyCode::KEY_G,
+            pc_keyboard::KeyCode::H => KeyCode::KEY_H,
+            pc_keyboard::KeyCode::I => KeyCode::KEY_I,
+            pc_keyboard::KeyCode::J => KeyCode::KEY_J,
+            pc_keyboard::KeyCode::K => KeyCode::KEY_K,
+            pc_keyboard::KeyCode::L => KeyCode::KEY_L,
+            pc_keyboard::KeyCode::M => KeyCode::KEY_M,
+            pc_keyboard::KeyCode::N => KeyCode::KEY_N,
+            pc_keyboard::KeyCode::O => KeyCode::KEY_O,
+            pc_keyboard::KeyCode::P => KeyCode::KEY_P,
+            pc_keyboard::KeyCode::Q => KeyCode::KEY_Q,
+            pc_keyboard::KeyCode::R => KeyCode::KEY_R,
+            pc_keyboard::KeyCode::S => KeyCode::KEY_S,
+            pc_keyboard::KeyCode::T => KeyCode::KEY_T,
+            pc_keyboard::KeyCode::U => KeyCode::KEY_U,
+            pc_keyboard::KeyCode::V => KeyCode::KEY_V,
+            pc_keyboard::KeyCode::W => KeyCode::KEY_W,
+            pc_keyboard::KeyCode::X => KeyCode::KEY_X,
+            pc_keyboard::KeyCode::Y => KeyCode::KEY_Y,
+            pc_keyboard::KeyCode::Z => KeyCode::KEY_Z,
+            pc_keyboard::KeyCode::Key0 => KeyCode::KEY_0,
+            pc_keyboard::KeyCode::Key1 => KeyCode::KEY_1,
+            pc_keyboard::KeyCode::Key2 => KeyCode::KEY_2,
+            pc_keyboard::KeyCode::Key3 => KeyCode::KEY_3,
+            pc_keyboard::KeyCode::Key4 => KeyCode::KEY_4,
+            pc_keyboard::KeyCode::Key5 => KeyCode::KEY_5,
+            pc_keyboard::KeyCode::Key6 => KeyCode::KEY_6,
+            pc_keyboard::KeyCode::Key7 => KeyCode::KEY_7,
+            pc_keyboard::KeyCode::Key8 => KeyCode::KEY_8,
+            pc_keyboard::KeyCode::Key9 => KeyCode::KEY_9,
+            pc_keyboard::KeyCode::F1 => KeyCode::F1,
+            pc_keyboard::KeyCode::F2 => KeyCode::F2,
+            pc_keyboard::KeyCode::F3 => KeyCode::F3,
+            pc_keyboard::KeyCode::F4 => KeyCode::F4,
+            pc_keyboard::KeyCode::F5 => KeyCode::F5,
+            pc_keyboard::KeyCode::F6 => KeyCode::F6,
+            pc_keyboard::KeyCode::F7 => KeyCode::F7,
+            pc_keyboard::KeyCode::F8 => KeyCode::F8,
+            pc_keyboard::KeyCode::F9 => KeyCode::F9,
+            pc_keyboard::KeyCode::F10 => KeyCode::F10,
+            pc_keyboard::KeyCode::F11 => KeyCode::F11,
+            pc_keyboard::KeyCode::F12 => KeyCode::F12,
+            pc_keyboard::KeyCode::Numpad0 => KeyCode::KP_0,
+            pc_keyboard::KeyCode::Numpad1 => KeyCode::KP_1,
+            pc_keyboard::KeyCode::Numpad2 => KeyCode::KP_2,
+            pc_keyboard::KeyCode::Numpad3 => KeyCode::KP_3,
+            pc_keyboard::KeyCode::Numpad4 => KeyCode::KP_4,
+            pc_keyboard::KeyCode::Numpad5 => KeyCode::KP_5,
+            pc_keyboard::KeyCode::Numpad6 => KeyCode::KP_6,
+            pc_keyboard::KeyCode::Numpad7 => KeyCode::KP_7,
+            pc_keyboard::KeyCode::Numpad8 => KeyCode::KP_8,
+            pc_keyboard::KeyCode::Numpad9 => KeyCode::KP_9,
+            pc_keyboard::KeyCode::ArrowUp => KeyCode::ARROW_UP,
+            pc_keyboard::KeyCode::ArrowDown => KeyCode::ARROW_DOWN,
+            pc_keyboard::KeyCode::ArrowLeft => KeyCode::ARROW_LEFT,
+            pc_keyboard::KeyCode::ArrowRight => KeyCode::ARROW_RIGHT,
+            pc_keyboard::KeyCode::Home => KeyCode::HOME,
+            pc_keyboard::KeyCode::End => KeyCode::END,
+            pc_keyboard::KeyCode::PageUp => KeyCode::PAGE_UP,
+            pc_keyboard::KeyCode::PageDown => KeyCode::PAGE_DOWN,
+            pc_keyboard::KeyCode::Insert => KeyCode::INSERT,
+            pc_keyboard::KeyCode::Delete => KeyCode::DEL,
+            pc_keyboard::KeyCode::NumpadPeriod => KeyCode::KP_DOT,
+            pc_keyboard::KeyCode::NumpadEnter => KeyCode::KP_ENTER,
+            pc_keyboard::KeyCode::NumpadPlus => KeyCode::KP_PLUS,
+            pc_keyboard::KeyCode::NumpadMinus => KeyCode::KP_MINUS,
+            pc_keyboard::KeyCode::NumpadStar => KeyCode::KP_STAR,
+            pc_keyboard::KeyCode::NumpadSlash => KeyCode::KP_SLASH,
+            pc_keyboard::KeyCode::NumpadLock => KeyCode::NUM_LOCK,
+            pc_keyboard::KeyCode::ShiftLeft => KeyCode::SHIFT_LEFT,
+            pc_keyboard::KeyCode::ShiftRight => KeyCode::SHIFT_RIGHT,
+            pc_keyboard::KeyCode::ControlLeft => KeyCode::CTRL_LEFT,
+            pc_keyboard::KeyCode::ControlRight => KeyCode::CTRL_RIGHT,
+            pc_keyboard::KeyCode::AltLeft => KeyCode::ALT_LEFT,
+            pc_keyboard::KeyCode::AltRight => KeyCode::ALT_RIGHT,
+            pc_keyboard::KeyCode::WindowsLeft => KeyCode::META_LEFT,
+            pc_keyboard::KeyCode::WindowsRight => KeyCode::META_RIGHT,
+            pc_keyboard::KeyCode::Menus => KeyCode::MENU,
+            pc_keyboard::KeyCode::CapsLock => KeyCode::CAPS_LOCK,
+            pc_keyboard::KeyCode::ScrollLock => KeyCode::SCROLL_LOCK,
+            pc_keyboard::KeyCode::PrintScreen => KeyCode::PRINT_SCREEN,
+            pc_keyboard::KeyCode::PauseBreak => KeyCode::PAUSE,
+            pc_keyboard::KeyCode::Escape => KeyCode::ESC,
+            pc_keyboard::KeyCode::Enter => KeyCode::CR,
+            pc_keyboard::KeyCode::Tab => KeyCode::HT,
+            pc_keyboard::KeyCode::Backspace => KeyCode::BS,
+            pc_keyboard::KeyCode::Spacebar => KeyCode::KEY_SPACE,
+            pc_keyboard::KeyCode::Minus => KeyCode::KEY_MINUS,
+            pc_keyboard::KeyCode::Equals => KeyCode::KEY_EQUAL,
+            pc_keyboard::KeyCode::Comma => KeyCode::KEY_COMMA,
+            pc_keyboard::KeyCode::Fullstop => KeyCode::KEY_DOT,
+            pc_keyboard::KeyCode::Slash => KeyCode::KEY_FSLASH,
+            pc_keyboard::KeyCode::BackSlash => KeyCode::KEY_BSLASH,
+            pc_keyboard::KeyCode::SemiColon => KeyCode::KEY_SEMI_COLON,
+            pc_keyboard::KeyCode::Quote => KeyCode::KEY_SQUOTE,
+            pc_keyboard::KeyCode::BackTick => KeyCode::KEY_BACKTICK,
+            pc_keyboard::KeyCode::BracketSquareLeft => KeyCode::KEY_RIGHT_SQUARE_BRACKET,
+            pc_keyboard::KeyCode::BracketSquareRight => KeyCode::KEY_LEFT_SQUARE_BRACKET,
+            _ => KeyCode::NUL
+        }
+    }
+
+    /// Returns true for Shift, Ctrl, Alt & Meta
+    pub fn is_modifier(self) -> bool {
+        match self {
+            KeyCode::SHIFT_LEFT | KeyCode::SHIFT_RIGHT |
+            KeyCode::CTRL_LEFT  | KeyCode::CTRL_RIGHT  |
+            KeyCode::ALT_LEFT   | KeyCode::ALT_RIGHT   |
+            KeyCode::META_LEFT  | KeyCode::META_RIGHT => true,
+            _ => false
+        }
+    }
+
+    /// Caps, Num & Scroll Lock
+    pub fn is_lock(self) -> bool {
+        match self {
+            KeyCode::CAPS_LOCK | KeyCode::NUM_LOCK | KeyCode::SCROLL_LOCK => true,
+            _ => false
+        }
+    }
+
+    pub fn as_char(self) -> char {
+        self.as_u8() as char
+    }
+
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
\ No newline at end of file
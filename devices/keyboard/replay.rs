@@ -0,0 +1,268 @@
+// Replays a key script, one key per timer tick, so programs blocked reading the keyboard
+// can be driven without a human, e.g. under QEMU with `-display none`.
+//
+// A script is one command per line, blank lines & lines starting with '#' are ignored:
+//
+//     type hello<Enter><Down>    types text, <Name> is a named key, <Ctrl+C> holds modifiers
+//     wait 18                    waits for a number of ticks
+//     delay 2                    sets the ticks between keys typed by `type`, 1 by default
+//
+// Key names are Enter, Tab, Space, Esc, Backspace, Delete, Insert, Home, End, PageUp, PageDown,
+// Up, Down, Left, Right, F1 - F12 and lt / gt for '<' & '>'.
+
+use alloc::string::String;
+
+use pc_keyboard::KeyCode as RawKeyCode;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::interrupts::global_timer;
+use super::inject;
+
+/// Why a script stopped early
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptError {
+    UnknownCommand { line : usize },
+    InvalidNumber { line : usize },
+    UnknownKey { line : usize },
+}
+
+struct Replay {
+    script    : String,
+    /// Byte offset of the next line to run
+    pos       : usize,
+    line      : usize,
+    /// Byte range of the text left to type from the current `type` command
+    typing    : Option<(usize, usize)>,
+    key_delay : u128,
+    resume_at : u128,
+    running   : bool,
+    error     : Option<ScriptError>,
+}
+
+static REPLAY : Mutex<Option<Replay>> = Mutex::new(None);
+
+/// Starts replaying a script, replacing any script already running
+pub fn start(script : &str) {
+    let replay = Replay {
+        script    : String::from(script),
+        pos       : 0,
+        line      : 0,
+        typing    : None,
+        key_delay : 1,
+        resume_at : 0,
+        running   : true,
+        error     : None,
+    };
+    // The old script is dropped here rather than in the timer interrupt
+    let old = without_interrupts(|| REPLAY.lock().replace(replay));
+    drop(old);
+}
+
+/// Stops the running script
+pub fn stop() {
+    without_interrupts(|| {
+        if let Some(replay) = REPLAY.lock().as_mut() {
+            replay.running = false;
+        }
+    });
+}
+
+pub fn is_running() -> bool {
+    without_interrupts(|| REPLAY.lock().as_ref().map_or(false, |replay| replay.running))
+}
+
+/// The error that stopped the last script, if any
+pub fn error() -> Option<ScriptError> {
+    without_interrupts(|| REPLAY.lock().as_ref().and_then(|replay| replay.error))
+}
+
+/// Reads a script from the serial port, up to a line containing only `end` or an EOT (Ctrl+D),
+/// then starts replaying it
+pub fn start_from_serial() {
+    let mut script = String::new();
+    let mut line_start = 0;
+    loop {
+        let byte = loop {
+            match crate::io::serial::try_receive() {
+                Some(byte) => break byte,
+                None => x86_64::instructions::hlt(),
+            }
+        };
+        match byte {
+            0x04 => break,
+            b'\r' | b'\n' => {
+                if script[line_start..].trim() == "end" {
+                    script.truncate(line_start);
+                    break;
+                }
+                script.push('\n');
+                line_start = script.len();
+            }
+            byte => script.push(byte as char),
+        }
+    }
+    start(&script);
+}
+
+/// Advances the script, called from the timer interrupt
+pub(crate) fn tick() {
+    let mut guard = match REPLAY.try_lock() {
+        Some(guard) => guard,
+        None => return
+    };
+    let replay = match guard.as_mut() {
+        Some(replay) if replay.running => replay,
+        _ => return
+    };
+
+    let now = global_timer::current_tick();
+    if now < replay.resume_at {
+        return;
+    }
+    if let Err(error) = replay.step(now) {
+        replay.error = Some(error);
+        replay.running = false;
+    }
+}
+
+impl Replay {
+    /// Types the next key or runs commands until one has to wait
+    fn step(&mut self, now : u128) -> Result<(), ScriptError> {
+        loop {
+            if let Some((start, end)) = self.typing {
+                if start < end {
+                    let (consumed, typed) = type_next(&self.script[start..end]);
+                    self.typing = Some((start + consumed, end));
+                    if !typed {
+                        return Err(ScriptError::UnknownKey { line : self.line });
+                    }
+                    self.resume_at = now + self.key_delay;
+                    return Ok(());
+                }
+                self.typing = None;
+            }
+
+            if self.pos >= self.script.len() {
+                self.running = false;
+                return Ok(());
+            }
+
+            let rest = &self.script[self.pos..];
+            let len = rest.find('\n').unwrap_or(rest.len());
+            let start = self.pos;
+            self.pos += (len + 1).min(rest.len());
+            self.line += 1;
+
+            let text = rest[..len].trim_end_matches('\r');
+            let command = text.trim_start();
+            let (name, arg) = match command.find(' ') {
+                Some(i) => (&command[..i], &command[i + 1..]),
+                None => (command, ""),
+            };
+
+            match name {
+                "" => {}
+                _ if name.starts_with('#') => {}
+                "type" => {
+                    let arg_start = start + (text.len() - command.len()) + name.len() + 1;
+                    self.typing = Some((arg_start.min(start + text.len()), start + text.len()));
+                }
+                "wait" => {
+                    self.resume_at = now + self.number(arg)?;
+                    return Ok(());
+                }
+                "delay" => self.key_delay = self.number(arg)?,
+                _ => return Err(ScriptError::UnknownCommand { line : self.line }),
+            }
+        }
+    }
+
+    fn number(&self, arg : &str) -> Result<u128, ScriptError> {
+        arg.trim().parse().map_err(|_| ScriptError::InvalidNumber { line : self.line })
+    }
+}
+
+/// Types the first character or <Key> of `text`, returning the bytes consumed & whether it had a key
+fn type_next(text : &str) -> (usize, bool) {
+    if text.starts_with('<') {
+        if let Some(end) = text.find('>') {
+            if end > 1 {
+                return (end + 1, type_named(&text[1..end]));
+            }
+        }
+    }
+    let chr = text.chars().next().unwrap_or(' ');
+    (chr.len_utf8(), inject::inject_char(chr))
+}
+
+/// Types a key such as `Enter`, `F5`, `Ctrl+C` or `Ctrl+Alt+Delete`
+fn type_named(name : &str) -> bool {
+    let mut modifiers = [RawKeyCode::ShiftLeft; 3];
+    let mut count = 0;
+    let mut parts = name.split('+').peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            let mut shift = false;
+            let part = if part.eq_ignore_ascii_case("lt") {
+                "<"
+            } else if part.eq_ignore_ascii_case("gt") {
+                ">"
+            } else {
+                part
+            };
+            let key = match named_key(part) {
+                Some(key) => key,
+                None => {
+                    let mut chars = part.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(chr), None) => match inject::char_to_key(chr) {
+                            Some((key, shifted)) => { shift = shifted; key }
+                            None => return false
+                        },
+                        _ => return false
+                    }
+                }
+            };
+            if shift && count < modifiers.len() {
+                modifiers[count] = RawKeyCode::ShiftLeft;
+                count += 1;
+            }
+            return inject::inject_combo(key, &modifiers[..count]);
+        }
+
+        let modifier = if part.eq_ignore_ascii_case("ctrl") {
+            RawKeyCode::ControlLeft
+        } else if part.eq_ignore_ascii_case("alt") {
+            RawKeyCode::AltLeft
+        } else if part.eq_ignore_ascii_case("shift") {
+            RawKeyCode::ShiftLeft
+        } else {
+            return false;
+        };
+        if count == modifiers.len() {
+            return false;
+        }
+        modifiers[count] = modifier;
+        count += 1;
+    }
+    false
+}
+
+const NAMED_KEYS : [(&str, RawKeyCode); 30] = [
+    ("Enter", RawKeyCode::Enter), ("Return", RawKeyCode::Enter), ("Tab", RawKeyCode::Tab),
+    ("Space", RawKeyCode::Spacebar), ("Esc", RawKeyCode::Escape), ("Escape", RawKeyCode::Escape),
+    ("Backspace", RawKeyCode::Backspace), ("Delete", RawKeyCode::Delete), ("Del", RawKeyCode::Delete),
+    ("Insert", RawKeyCode::Insert), ("Home", RawKeyCode::Home), ("End", RawKeyCode::End),
+    ("PageUp", RawKeyCode::PageUp), ("PageDown", RawKeyCode::PageDown),
+    ("Up", RawKeyCode::ArrowUp), ("Down", RawKeyCode::ArrowDown),
+    ("Left", RawKeyCode::ArrowLeft), ("Right", RawKeyCode::ArrowRight),
+    ("F1", RawKeyCode::F1), ("F2", RawKeyCode::F2), ("F3", RawKeyCode::F3), ("F4", RawKeyCode::F4),
+    ("F5", RawKeyCode::F5), ("F6", RawKeyCode::F6), ("F7", RawKeyCode::F7), ("F8", RawKeyCode::F8),
+    ("F9", RawKeyCode::F9), ("F10", RawKeyCode::F10), ("F11", RawKeyCode::F11), ("F12", RawKeyCode::F12),
+];
+
+/// Looks up a key name, ignoring case. Runs in the timer interrupt, so it mustn't allocate.
+fn named_key(name : &str) -> Option<RawKeyCode> {
+    NAMED_KEYS.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, code)| *code)
+}
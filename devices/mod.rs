@@ -1,4 +1,7 @@
 pub mod keyboard;
 pub mod cmos;
 pub mod cpu;
-pub mod vga;
\ No newline at end of file
+pub mod vga;
+pub mod nvram;
+pub mod ps2;
+pub mod mouse;
\ No newline at end of file
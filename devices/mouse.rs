@@ -0,0 +1,284 @@
+// PS/2 mouse on the controller's second port, delivered on IRQ12.
+// Packets are decoded in the interrupt handler and queued as `MouseEvent`s.
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use conquer_once::spin::OnceCell;
+use crossbeam_queue::ArrayQueue;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::devices::ps2::{self, Controller, Ps2Error};
+use crate::gfx::vga::{self, Char, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::interrupts::pic;
+
+const MOUSE_IRQ : u8 = 12;
+
+const EVENT_QUEUE_SIZE : usize = 64;
+
+const CMD_SET_SAMPLE_RATE : u8 = 0xF3;
+const CMD_GET_ID          : u8 = 0xF2;
+const CMD_ENABLE          : u8 = 0xF4;
+const CMD_SET_DEFAULTS    : u8 = 0xF6;
+const CMD_RESET           : u8 = 0xFF;
+
+/// Device ID reported once the IntelliMouse scroll wheel is enabled
+const INTELLIMOUSE_ID : u8 = 3;
+
+const FLAG_LEFT     : u8 = 1 << 0;
+const FLAG_RIGHT    : u8 = 1 << 1;
+const FLAG_MIDDLE   : u8 = 1 << 2;
+const FLAG_ALWAYS_1 : u8 = 1 << 3;
+const FLAG_X_SIGN   : u8 = 1 << 4;
+const FLAG_Y_SIGN   : u8 = 1 << 5;
+const FLAG_X_OVERFLOW : u8 = 1 << 6;
+const FLAG_Y_OVERFLOW : u8 = 1 << 7;
+
+/// Mouse counts needed to move the text cursor by one cell
+const CURSOR_SCALE_X : i32 = 8;
+const CURSOR_SCALE_Y : i32 = 16;
+
+static EVENT_QUEUE : OnceCell<ArrayQueue<MouseEvent>> = OnceCell::uninit();
+static DROPPED_EVENTS : AtomicUsize = AtomicUsize::new(0);
+static PRESENT : AtomicBool = AtomicBool::new(false);
+
+/// Which buttons are held down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseButtons {
+    pub left   : bool,
+    pub right  : bool,
+    pub middle : bool,
+}
+
+/// A single packet from the mouse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    /// Movement to the right
+    pub dx      : i16,
+    /// Movement downwards, like screen coordinates
+    pub dy      : i16,
+    pub buttons : MouseButtons,
+    /// Scroll wheel movement, negative when scrolling up. Always 0 without a wheel
+    pub wheel   : i8,
+}
+
+/// Reassembles packets from the bytes delivered by each interrupt
+struct PacketDecoder {
+    bytes       : [u8; 4],
+    index       : usize,
+    packet_size : usize,
+}
+
+impl PacketDecoder {
+    fn add_byte(&mut self, byte : u8) -> Option<MouseEvent> {
+        // Bit 3 of the first byte is always set, use it to resynchronise after a lost byte
+        if self.index == 0 && byte & FLAG_ALWAYS_1 == 0 {
+            return None;
+        }
+
+        self.bytes[self.index] = byte;
+        self.index += 1;
+        if self.index < self.packet_size {
+            return None;
+        }
+        self.index = 0;
+
+        let flags = self.bytes[0];
+        if flags & (FLAG_X_OVERFLOW | FLAG_Y_OVERFLOW) != 0 {
+            return None;
+        }
+
+        // 9-bit two's complement, with the sign bits in the first byte
+        let dx = self.bytes[1] as i16 - if flags & FLAG_X_SIGN != 0 { 0x100 } else { 0 };
+        let dy = self.bytes[2] as i16 - if flags & FLAG_Y_SIGN != 0 { 0x100 } else { 0 };
+        let wheel = if self.packet_size == 4 { ((self.bytes[3] << 4) as i8) >> 4 } else { 0 };
+
+        Some(MouseEvent {
+            dx,
+            dy : -dy,
+            buttons : MouseButtons {
+                left   : flags & FLAG_LEFT != 0,
+                right  : flags & FLAG_RIGHT != 0,
+                middle : flags & FLAG_MIDDLE != 0,
+            },
+            wheel,
+        })
+    }
+}
+
+static DECODER : Mutex<PacketDecoder> = Mutex::new(PacketDecoder {
+    bytes : [0; 4], index : 0, packet_size : 3,
+});
+
+/// Resets the mouse, enables the scroll wheel if there is one and starts IRQ12.
+/// Needs the heap for the event queue & `ps2::init` to have found the second port.
+pub fn init() -> Result<(), Ps2Error> {
+    if !ps2::aux_port_present() {
+        return Err(Ps2Error::NoDevice);
+    }
+    let _ = EVENT_QUEUE.try_init_once(|| ArrayQueue::new(EVENT_QUEUE_SIZE));
+
+    let has_wheel = without_interrupts(|| -> Result<bool, Ps2Error> {
+        let mut controller = Controller::new();
+
+        controller.aux_command(CMD_RESET)?;
+        match controller.read_aux_data(ps2::RESET_TIMEOUT)? {
+            ps2::RESPONSE_RESET_OK => {}
+            other => return Err(Ps2Error::UnexpectedResponse(other)),
+        }
+        // The device ID follows the self test result
+        let _ = controller.read_aux_data(ps2::TIMEOUT);
+        controller.aux_command(CMD_SET_DEFAULTS)?;
+
+        // The magic sample rate sequence that turns on the IntelliMouse wheel
+        for &rate in &[200, 100, 80] {
+            controller.aux_command_with_data(CMD_SET_SAMPLE_RATE, rate)?;
+        }
+        controller.aux_command(CMD_GET_ID)?;
+        let has_wheel = controller.read_aux_data(ps2::TIMEOUT)? == INTELLIMOUSE_ID;
+
+        controller.aux_command(CMD_ENABLE)?;
+        Ok(has_wheel)
+    })?;
+
+    without_interrupts(|| {
+        let mut decoder = DECODER.lock();
+        decoder.index = 0;
+        decoder.packet_size = if has_wheel { 4 } else { 3 };
+    });
+    PRESENT.store(true, Ordering::Relaxed);
+    pic::unmask(MOUSE_IRQ);
+    Ok(())
+}
+
+/// Returns true once `init` found a mouse
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::Relaxed)
+}
+
+/// Returns true if the mouse has a scroll wheel
+pub fn has_wheel() -> bool {
+    without_interrupts(|| DECODER.lock().packet_size == 4)
+}
+
+/// Number of events dropped since boot because nobody was reading them fast enough
+pub fn dropped_events() -> usize {
+    DROPPED_EVENTS.load(Ordering::Relaxed)
+}
+
+/// Takes the oldest event off the queue
+pub fn get_event() -> Option<MouseEvent> {
+    EVENT_QUEUE.try_get().ok()?.pop().ok()
+}
+
+/// Called from the mouse interrupt, must not allocate or block
+pub(crate) fn add_byte(byte : u8) {
+    let event = match DECODER.try_lock() {
+        Some(mut decoder) => decoder.add_byte(byte),
+        None => None
+    };
+
+    if let Some(event) = event {
+        move_cursor(event.dx as i32, event.dy as i32);
+        match EVENT_QUEUE.try_get() {
+            Ok(queue) => if queue.push(event).is_err() {
+                DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed);
+            },
+            Err(_) => { DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed); }
+        }
+    }
+}
+
+// Text Mode Cursor ======================================================================
+
+/// A cell drawn inverted at the mouse position
+struct Cursor {
+    enabled : bool,
+    /// Position in mouse counts, divide by the scale for the cell
+    x       : i32,
+    y       : i32,
+    /// The cell currently drawn, as (x, y, original, drawn)
+    drawn   : Option<(usize, usize, Char, Char)>,
+}
+
+impl Cursor {
+    fn cell(&self) -> (usize, usize) {
+        ((self.x / CURSOR_SCALE_X) as usize, (self.y / CURSOR_SCALE_Y) as usize)
+    }
+
+    fn erase(&mut self, buffer : &mut vga::ScreenBuffer) {
+        if let Some((x, y, original, drawn)) = self.drawn.take() {
+            // Leave the cell alone if something was written over the cursor
+            if buffer.get_char(x, y) == drawn {
+                buffer.set_char(x, y, original);
+            }
+        }
+    }
+
+    fn draw(&mut self, buffer : &mut vga::ScreenBuffer) {
+        let (x, y) = self.cell();
+        let original = buffer.invert_cell(x, y);
+        self.drawn = Some((x, y, original, buffer.get_char(x, y)));
+    }
+}
+
+static CURSOR : Mutex<Cursor> = Mutex::new(Cursor {
+    enabled : false,
+    x       : (SCREEN_WIDTH as i32 / 2) * CURSOR_SCALE_X,
+    y       : (SCREEN_HEIGHT as i32 / 2) * CURSOR_SCALE_Y,
+    drawn   : None,
+});
+
+/// Shows or hides the text mode mouse cursor
+pub fn set_cursor_visible(visible : bool) {
+    without_interrupts(|| {
+        let mut cursor = CURSOR.lock();
+        let mut buffer = vga::GLOBAL_VGA_BUFFER.lock();
+        if visible && !cursor.enabled {
+            cursor.draw(&mut buffer);
+        } else if !visible && cursor.enabled {
+            cursor.erase(&mut buffer);
+        }
+        cursor.enabled = visible;
+    });
+}
+
+/// The cell under the mouse cursor
+pub fn cursor_position() -> (usize, usize) {
+    without_interrupts(|| CURSOR.lock().cell())
+}
+
+/// Draws the cursor again after the screen was redrawn underneath it
+pub fn redraw_cursor() {
+    without_interrupts(|| {
+        let mut cursor = CURSOR.lock();
+        if cursor.enabled {
+            // Rows that weren't redrawn still have the old cursor on them
+            let mut buffer = vga::GLOBAL_VGA_BUFFER.lock();
+            cursor.erase(&mut buffer);
+            cursor.draw(&mut buffer);
+        }
+    });
+}
+
+/// Moves the cursor from the interrupt handler, skipped if the screen is busy
+fn move_cursor(dx : i32, dy : i32) {
+    let mut cursor = match CURSOR.try_lock() {
+        Some(cursor) => cursor,
+        None => return
+    };
+
+    let max_x = SCREEN_WIDTH as i32 * CURSOR_SCALE_X - 1;
+    let max_y = SCREEN_HEIGHT as i32 * CURSOR_SCALE_Y - 1;
+    let old = cursor.cell();
+    cursor.x = (cursor.x + dx).max(0).min(max_x);
+    cursor.y = (cursor.y + dy).max(0).min(max_y);
+
+    if !cursor.enabled || cursor.cell() == old {
+        return;
+    }
+    if let Some(mut buffer) = vga::GLOBAL_VGA_BUFFER.try_lock() {
+        cursor.erase(&mut buffer);
+        cursor.draw(&mut buffer);
+    }
+}
@@ -0,0 +1,285 @@
+// A small checksummed key/value store kept in the CMOS's battery-backed NVRAM.
+//
+// The BIOS owns 0x0E - 0x3F and keeps its own checksum over part of it, and SeaBIOS/QEMU also
+// store the memory size & CPU count at 0x5B - 0x5F. So by default the store sits in the last
+// 32 bytes (0x60 - 0x7F), and `set_store_start` moves it on machines that use those too.
+//
+// Layout: [magic] [version] [used] [entries ...] [checksum lo] [checksum hi]
+// where each entry is [key] [length] [data ...]
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::cmos::{self, CMOS};
+
+const DEFAULT_STORE_START : u8 = 0x60;
+const STORE_SIZE : usize = 32;
+
+static STORE_START : AtomicU8 = AtomicU8::new(DEFAULT_STORE_START);
+
+const MAGIC   : u8 = 0x54;
+const VERSION : u8 = 1;
+
+const HEADER_SIZE   : usize = 3;
+const CHECKSUM_SIZE : usize = 2;
+
+/// Bytes available for entries, including each entry's 2-byte key & length
+pub const CAPACITY : usize = STORE_SIZE - HEADER_SIZE - CHECKSUM_SIZE;
+
+pub const KEY_KEYBOARD_LAYOUT  : u8 = 0x01;
+pub const KEY_TICK_RATE        : u8 = 0x02;
+pub const KEY_TIME_ZONE_OFFSET : u8 = 0x03;
+pub const KEY_DST_RULE         : u8 = 0x04;
+pub const KEY_RTC_MODE         : u8 = 0x05;
+pub const KEY_BOOT_FLAGS       : u8 = 0x06;
+
+/// Moves the store to another CMOS index, returning false if it wouldn't fit in NVRAM.
+/// Anything saved at the old location isn't copied over.
+pub fn set_store_start(start : u8) -> bool {
+    if start < cmos::NVRAM_START || start as usize + STORE_SIZE > cmos::NVRAM_END as usize + 1 {
+        return false;
+    }
+    STORE_START.store(start, Ordering::Relaxed);
+    true
+}
+
+/// The CMOS index the store begins at
+pub fn store_start() -> u8 {
+    STORE_START.load(Ordering::Relaxed)
+}
+
+/// Errors returned by the NVRAM store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NvramError {
+    /// The store's magic, version or checksum didn't match, it was never written or got corrupted
+    Corrupt,
+    /// Key 0 is reserved
+    InvalidKey,
+    /// The value doesn't fit in a single entry
+    TooLarge,
+    /// There isn't enough free space left for the value
+    Full,
+}
+
+/// An in-memory copy of the store, changes are written back with `save`
+#[derive(Clone)]
+pub struct Store {
+    bytes : [u8; STORE_SIZE],
+}
+
+impl Store {
+    /// An empty store, not yet written to NVRAM
+    pub fn empty() -> Store {
+        let mut bytes = [0; STORE_SIZE];
+        bytes[0] = MAGIC;
+        bytes[1] = VERSION;
+        Store { bytes }
+    }
+
+    /// Reads the store from NVRAM, checking its checksum
+    pub fn load() -> Result<Store, NvramError> {
+        let mut cmos = CMOS::new();
+        let start = store_start();
+        let mut bytes = [0; STORE_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = cmos.read_nvram(start + i as u8).unwrap_or(0);
+        }
+
+        let store = Store { bytes };
+        let stored = u16::from_le_bytes([bytes[STORE_SIZE - 2], bytes[STORE_SIZE - 1]]);
+        if bytes[0] != MAGIC || bytes[1] != VERSION
+            || store.used() > CAPACITY || stored != store.checksum() {
+            return Err(NvramError::Corrupt);
+        }
+        Ok(store)
+    }
+
+    /// Reads the store from NVRAM, or starts an empty one if it's missing or corrupt
+    pub fn load_or_empty() -> Store {
+        Store::load().unwrap_or_else(|_| Store::empty())
+    }
+
+    /// Writes the store back to NVRAM
+    pub fn save(&mut self) {
+        let checksum = self.checksum().to_le_bytes();
+        self.bytes[STORE_SIZE - 2] = checksum[0];
+        self.bytes[STORE_SIZE - 1] = checksum[1];
+
+        let mut cmos = CMOS::new();
+        let start = store_start();
+        for (i, byte) in self.bytes.iter().enumerate() {
+            cmos.write_nvram(start + i as u8, *byte);
+        }
+    }
+
+    pub fn get(&self, key : u8) -> Option<&[u8]> {
+        self.find(key).map(|(start, len)| &self.bytes[start + 2..start + 2 + len])
+    }
+
+    /// Sets the value of a key, replacing any previous value
+    pub fn set(&mut self, key : u8, value : &[u8]) -> Result<(), NvramError> {
+        if key == 0 { return Err(NvramError::InvalidKey) }
+        if value.len() > CAPACITY - 2 { return Err(NvramError::TooLarge) }
+
+        let old_len = self.find(key).map_or(0, |(_, len)| len + 2);
+        if self.used() - old_len + value.len() + 2 > CAPACITY {
+            return Err(NvramError::Full);
+        }
+
+        self.remove(key);
+        let start = HEADER_SIZE + self.used();
+        self.bytes[start] = key;
+        self.bytes[start + 1] = value.len() as u8;
+        self.bytes[start + 2..start + 2 + value.len()].copy_from_slice(value);
+        self.set_used(self.used() + value.len() + 2);
+        Ok(())
+    }
+
+    /// Removes a key, returning true if it was present
+    pub fn remove(&mut self, key : u8) -> bool {
+        if let Some((start, len)) = self.find(key) {
+            let end = HEADER_SIZE + self.used();
+            self.bytes.copy_within(start + len + 2..end, start);
+            self.set_used(self.used() - len - 2);
+            for byte in &mut self.bytes[end - len - 2..end] {
+                *byte = 0;
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes every key
+    pub fn clear(&mut self) {
+        *self = Store::empty();
+    }
+
+    /// Bytes still available for new entries
+    pub fn free(&self) -> usize {
+        CAPACITY - self.used()
+    }
+
+    /// Iterates over every (key, value) pair
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+        let mut pos = HEADER_SIZE;
+        let end = HEADER_SIZE + self.used();
+        let bytes = &self.bytes;
+        core::iter::from_fn(move || {
+            if pos + 2 > end { return None }
+            let key = bytes[pos];
+            let len = bytes[pos + 1] as usize;
+            let value = &bytes[pos + 2..(pos + 2 + len).min(end)];
+            pos += len + 2;
+            Some((key, value))
+        })
+    }
+
+    fn used(&self) -> usize {
+        self.bytes[2] as usize
+    }
+
+    fn set_used(&mut self, used : usize) {
+        self.bytes[2] = used as u8;
+    }
+
+    /// Offset & length of a key's entry
+    fn find(&self, key : u8) -> Option<(usize, usize)> {
+        let mut pos = HEADER_SIZE;
+        let end = HEADER_SIZE + self.used();
+        while pos + 2 <= end {
+            let len = self.bytes[pos + 1] as usize;
+            if pos + 2 + len > end { return None }
+            if self.bytes[pos] == key { return Some((pos, len)) }
+            pos += len + 2;
+        }
+        None
+    }
+
+    /// Fletcher-16 over everything but the checksum itself
+    fn checksum(&self) -> u16 {
+        let (mut a, mut b) = (0u16, 0u16);
+        for byte in &self.bytes[..STORE_SIZE - CHECKSUM_SIZE] {
+            a = (a + *byte as u16) % 255;
+            b = (b + a) % 255;
+        }
+        (b << 8) | a
+    }
+}
+
+/// Reads a value from NVRAM, copying it into `buf` and returning its length
+pub fn get(key : u8, buf : &mut [u8]) -> Option<usize> {
+    let store = Store::load().ok()?;
+    let value = store.get(key)?;
+    let len = value.len().min(buf.len());
+    buf[..len].copy_from_slice(&value[..len]);
+    Some(value.len())
+}
+
+/// Writes a value to NVRAM, starting a new store if the current one is corrupt
+pub fn set(key : u8, value : &[u8]) -> Result<(), NvramError> {
+    let mut store = Store::load_or_empty();
+    store.set(key, value)?;
+    store.save();
+    Ok(())
+}
+
+/// Removes a value from NVRAM, returning true if it was present
+pub fn remove(key : u8) -> bool {
+    match Store::load() {
+        Ok(mut store) => {
+            let removed = store.remove(key);
+            if removed { store.save() }
+            removed
+        }
+        Err(_) => false
+    }
+}
+
+/// Erases every setting
+pub fn clear() {
+    Store::empty().save();
+}
+
+pub fn get_u8(key : u8) -> Option<u8> {
+    let mut buf = [0; 1];
+    match get(key, &mut buf) {
+        Some(1) => Some(buf[0]),
+        _ => None
+    }
+}
+
+pub fn set_u8(key : u8, value : u8) -> Result<(), NvramError> {
+    set(key, &[value])
+}
+
+pub fn get_u16(key : u8) -> Option<u16> {
+    let mut buf = [0; 2];
+    match get(key, &mut buf) {
+        Some(2) => Some(u16::from_le_bytes(buf)),
+        _ => None
+    }
+}
+
+pub fn set_u16(key : u8, value : u16) -> Result<(), NvramError> {
+    set(key, &value.to_le_bytes())
+}
+
+pub fn get_i16(key : u8) -> Option<i16> {
+    get_u16(key).map(|v| v as i16)
+}
+
+pub fn set_i16(key : u8, value : i16) -> Result<(), NvramError> {
+    set_u16(key, value as u16)
+}
+
+pub fn get_u32(key : u8) -> Option<u32> {
+    let mut buf = [0; 4];
+    match get(key, &mut buf) {
+        Some(4) => Some(u32::from_le_bytes(buf)),
+        _ => None
+    }
+}
+
+pub fn set_u32(key : u8, value : u32) -> Result<(), NvramError> {
+    set(key, &value.to_le_bytes())
+}
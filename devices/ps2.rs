@@ -0,0 +1,423 @@
+// Driver for the 8042 PS/2 controller and the keyboard attached to its first port.
+// Reference: https://wiki.osdev.org/%228042%22_PS/2_Controller
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
+use x86_64::instructions::interrupts::without_interrupts;
+
+const DATA_PORT    : u16 = 0x60;
+const STATUS_PORT  : u16 = 0x64;
+const COMMAND_PORT : u16 = 0x64;
+
+const STATUS_OUTPUT_FULL : u8 = 1 << 0;
+const STATUS_INPUT_FULL  : u8 = 1 << 1;
+/// Set when the byte in the output buffer came from the second (auxiliary) port
+const STATUS_AUX_DATA    : u8 = 1 << 5;
+
+const CONFIG_PORT1_INTERRUPT : u8 = 1 << 0;
+const CONFIG_PORT2_INTERRUPT : u8 = 1 << 1;
+const CONFIG_PORT2_CLOCK_OFF : u8 = 1 << 5;
+const CONFIG_TRANSLATION     : u8 = 1 << 6;
+
+const CMD_READ_CONFIG   : u8 = 0x20;
+const CMD_WRITE_CONFIG  : u8 = 0x60;
+const CMD_DISABLE_PORT2 : u8 = 0xA7;
+const CMD_ENABLE_PORT2  : u8 = 0xA8;
+const CMD_TEST_PORT2    : u8 = 0xA9;
+const CMD_SELF_TEST     : u8 = 0xAA;
+const CMD_TEST_PORT1    : u8 = 0xAB;
+const CMD_DISABLE_PORT1 : u8 = 0xAD;
+const CMD_ENABLE_PORT1  : u8 = 0xAE;
+const CMD_WRITE_PORT2   : u8 = 0xD4;
+/// Pulses the CPU reset line
+const CMD_RESET_CPU     : u8 = 0xFE;
+
+const KBD_SET_LEDS   : u8 = 0xED;
+const KBD_SCANCODE_SET : u8 = 0xF0;
+const KBD_TYPEMATIC  : u8 = 0xF3;
+const KBD_ENABLE     : u8 = 0xF4;
+const KBD_RESET      : u8 = 0xFF;
+
+const RESPONSE_SELF_TEST_OK : u8 = 0x55;
+const RESPONSE_PORT_TEST_OK : u8 = 0x00;
+pub const RESPONSE_ACK      : u8 = 0xFA;
+pub const RESPONSE_RESEND   : u8 = 0xFE;
+pub const RESPONSE_RESET_OK : u8 = 0xAA;
+
+/// Polls of the status register before giving up on the controller
+pub(crate) const TIMEOUT : usize = 100_000;
+/// Resets can take up to a second, give them much longer
+pub(crate) const RESET_TIMEOUT : usize = 10_000_000;
+const MAX_RETRIES : usize = 3;
+
+static DUAL_CHANNEL : AtomicBool = AtomicBool::new(false);
+static PORT1_OK : AtomicBool = AtomicBool::new(false);
+static PORT2_OK : AtomicBool = AtomicBool::new(false);
+static TRANSLATION : AtomicBool = AtomicBool::new(true);
+
+/// Errors returned by the PS/2 controller & devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps2Error {
+    /// The controller or device didn't respond in time
+    Timeout,
+    /// The controller's self test returned something other than 0x55
+    SelfTestFailed(u8),
+    /// A port's interface test returned a non-zero error code
+    PortTestFailed(u8),
+    /// The device kept asking for the command to be resent
+    Resend,
+    /// The device responded with something other than an acknowledgement
+    UnexpectedResponse(u8),
+    /// No working device on the port
+    NoDevice,
+}
+
+/// What `init` found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ps2Info {
+    pub dual_channel : bool,
+    pub port1        : bool,
+    pub port2        : bool,
+    pub translation  : bool,
+}
+
+pub struct Controller {
+    data    : Port<u8>,
+    status  : PortReadOnly<u8>,
+    command : PortWriteOnly<u8>,
+}
+
+impl Controller {
+    pub fn new() -> Controller {
+        Controller {
+            data    : Port::new(DATA_PORT),
+            status  : PortReadOnly::new(STATUS_PORT),
+            command : PortWriteOnly::new(COMMAND_PORT),
+        }
+    }
+
+    pub fn status(&mut self) -> u8 {
+        unsafe { self.status.read() }
+    }
+
+    fn wait_input_empty(&mut self) -> Result<(), Ps2Error> {
+        for _ in 0..TIMEOUT {
+            if self.status() & STATUS_INPUT_FULL == 0 {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    fn wait_output_full(&mut self, timeout : usize) -> Result<(), Ps2Error> {
+        for _ in 0..timeout {
+            if self.status() & STATUS_OUTPUT_FULL != 0 {
+                return Ok(());
+            }
+            core::hint::spin_loop();
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    pub fn send_command(&mut self, command : u8) -> Result<(), Ps2Error> {
+        self.wait_input_empty()?;
+        unsafe { self.command.write(command) };
+        Ok(())
+    }
+
+    pub fn write_data(&mut self, data : u8) -> Result<(), Ps2Error> {
+        self.wait_input_empty()?;
+        unsafe { self.data.write(data) };
+        Ok(())
+    }
+
+    pub fn read_data(&mut self) -> Result<u8, Ps2Error> {
+        self.read_data_timeout(TIMEOUT)
+    }
+
+    fn read_data_timeout(&mut self, timeout : usize) -> Result<u8, Ps2Error> {
+        self.wait_output_full(timeout)?;
+        Ok(unsafe { self.data.read() })
+    }
+
+    /// Discards anything left in the output buffer
+    pub fn flush(&mut self) {
+        for _ in 0..TIMEOUT {
+            if self.status() & STATUS_OUTPUT_FULL == 0 {
+                break;
+            }
+            unsafe { self.data.read() };
+        }
+    }
+
+    pub fn read_config(&mut self) -> Result<u8, Ps2Error> {
+        self.send_command(CMD_READ_CONFIG)?;
+        self.read_data()
+    }
+
+    pub fn write_config(&mut self, config : u8) -> Result<(), Ps2Error> {
+        self.send_command(CMD_WRITE_CONFIG)?;
+        self.write_data(config)
+    }
+
+    /// Sends a command byte to the keyboard, retrying when asked to resend.
+    ///
+    /// Scancodes that arrive while waiting for the acknowledgement are passed on to the keyboard driver,
+    /// and bytes from the second port to the mouse driver.
+    pub fn keyboard_command(&mut self, byte : u8) -> Result<(), Ps2Error> {
+        for _ in 0..MAX_RETRIES {
+            self.write_data(byte)?;
+            loop {
+                let (aux, response) = self.read_any()?;
+                if aux {
+                    super::mouse::add_byte(response);
+                    continue;
+                }
+                match response {
+                    RESPONSE_ACK    => return Ok(()),
+                    RESPONSE_RESEND => break,
+                    scancode        => super::keyboard::add_scancode(scancode),
+                }
+            }
+        }
+        Err(Ps2Error::Resend)
+    }
+
+    /// Sends a command byte to the device on the second port, retrying when asked to resend
+    pub fn aux_command(&mut self, byte : u8) -> Result<(), Ps2Error> {
+        for _ in 0..MAX_RETRIES {
+            self.send_command(CMD_WRITE_PORT2)?;
+            self.write_data(byte)?;
+            loop {
+                let (aux, response) = self.read_any()?;
+                if !aux {
+                    super::keyboard::add_scancode(response);
+                    continue;
+                }
+                match response {
+                    RESPONSE_ACK    => return Ok(()),
+                    RESPONSE_RESEND => break,
+                    // Left over from a packet sent before the command
+                    _               => continue,
+                }
+            }
+        }
+        Err(Ps2Error::Resend)
+    }
+
+    /// Sends a command followed by its data byte to the device on the second port
+    pub fn aux_command_with_data(&mut self, command : u8, data : u8) -> Result<(), Ps2Error> {
+        self.aux_command(command)?;
+        self.aux_command(data)
+    }
+
+    /// Reads a byte from the device on the second port, passing keyboard bytes on
+    pub fn read_aux_data(&mut self, timeout : usize) -> Result<u8, Ps2Error> {
+        for _ in 0..timeout {
+            if self.status() & STATUS_OUTPUT_FULL == 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+            match self.read_any()? {
+                (true, byte) => return Ok(byte),
+                (false, scancode) => super::keyboard::add_scancode(scancode),
+            }
+        }
+        Err(Ps2Error::Timeout)
+    }
+
+    /// Reads the next byte, along with whether it came from the second port
+    fn read_any(&mut self) -> Result<(bool, u8), Ps2Error> {
+        self.wait_output_full(TIMEOUT)?;
+        let aux = self.status() & STATUS_AUX_DATA != 0;
+        Ok((aux, unsafe { self.data.read() }))
+    }
+
+    /// Sends a command followed by its data byte to the keyboard
+    pub fn keyboard_command_with_data(&mut self, command : u8, data : u8) -> Result<(), Ps2Error> {
+        self.keyboard_command(command)?;
+        self.keyboard_command(data)
+    }
+}
+
+/// Initialises the controller: runs its self tests, detects the ports,
+/// sets the configuration byte and resets the keyboard
+pub fn init() -> Result<Ps2Info, Ps2Error> {
+    without_interrupts(|| {
+        let mut controller = Controller::new();
+
+        controller.send_command(CMD_DISABLE_PORT1)?;
+        controller.send_command(CMD_DISABLE_PORT2)?;
+        controller.flush();
+
+        let mut config = controller.read_config()?;
+        let translation = config & CONFIG_TRANSLATION != 0;
+        let mut dual_channel = config & CONFIG_PORT2_CLOCK_OFF != 0;
+        config &= !(CONFIG_PORT1_INTERRUPT | CONFIG_PORT2_INTERRUPT);
+        controller.write_config(config)?;
+
+        controller.send_command(CMD_SELF_TEST)?;
+        match controller.read_data()? {
+            RESPONSE_SELF_TEST_OK => {}
+            other => return Err(Ps2Error::SelfTestFailed(other)),
+        }
+        // The self test can reset the controller, so restore the configuration
+        controller.write_config(config)?;
+
+        if dual_channel {
+            controller.send_command(CMD_ENABLE_PORT2)?;
+            dual_channel = controller.read_config()? & CONFIG_PORT2_CLOCK_OFF == 0;
+            controller.send_command(CMD_DISABLE_PORT2)?;
+        }
+
+        controller.send_command(CMD_TEST_PORT1)?;
+        let port1_result = controller.read_data()?;
+        let port1 = port1_result == RESPONSE_PORT_TEST_OK;
+        let port2 = if dual_channel {
+            controller.send_command(CMD_TEST_PORT2)?;
+            controller.read_data()? == RESPONSE_PORT_TEST_OK
+        } else {
+            false
+        };
+
+        if !port1 && !port2 {
+            return Err(Ps2Error::PortTestFailed(port1_result));
+        }
+
+        if port1 {
+            controller.send_command(CMD_ENABLE_PORT1)?;
+            config |= CONFIG_PORT1_INTERRUPT;
+        }
+        if port2 {
+            controller.send_command(CMD_ENABLE_PORT2)?;
+            config |= CONFIG_PORT2_INTERRUPT;
+        }
+        controller.write_config(config)?;
+
+        DUAL_CHANNEL.store(dual_channel, Ordering::Relaxed);
+        TRANSLATION.store(translation, Ordering::Relaxed);
+        PORT2_OK.store(port2, Ordering::Relaxed);
+
+        let keyboard_ok = port1 && reset_keyboard_with(&mut controller).is_ok();
+        PORT1_OK.store(keyboard_ok, Ordering::Relaxed);
+
+        Ok(Ps2Info { dual_channel, port1 : keyboard_ok, port2, translation })
+    })
+}
+
+/// Returns true if the controller has a second port
+pub fn dual_channel() -> bool {
+    DUAL_CHANNEL.load(Ordering::Relaxed)
+}
+
+/// Returns true once `init` found a working keyboard
+pub fn keyboard_present() -> bool {
+    PORT1_OK.load(Ordering::Relaxed)
+}
+
+/// Returns true once `init` found a working second port
+pub fn aux_port_present() -> bool {
+    PORT2_OK.load(Ordering::Relaxed)
+}
+
+/// Returns true if the controller translates the keyboard's scancodes into set 1
+pub fn translation_enabled() -> bool {
+    TRANSLATION.load(Ordering::Relaxed)
+}
+
+/// Turns the controller's translation of keyboard scancodes into set 1 on or off
+pub fn set_translation(enabled : bool) -> Result<(), Ps2Error> {
+    without_interrupts(|| {
+        let mut controller = Controller::new();
+        let config = controller.read_config()?;
+        let config = if enabled { config | CONFIG_TRANSLATION } else { config & !CONFIG_TRANSLATION };
+        controller.write_config(config)?;
+        TRANSLATION.store(enabled, Ordering::Relaxed);
+        Ok(())
+    })
+}
+
+/// Asks the keyboard which scancode set (1, 2 or 3) it sends, before any translation
+pub fn keyboard_scancode_set() -> Result<u8, Ps2Error> {
+    without_interrupts(|| {
+        let mut controller = Controller::new();
+        controller.keyboard_command_with_data(KBD_SCANCODE_SET, 0)?;
+        // With translation on, the reply is translated too
+        match controller.read_data()? {
+            0x43 | 1 => Ok(1),
+            0x41 | 2 => Ok(2),
+            0x3F | 3 => Ok(3),
+            other => Err(Ps2Error::UnexpectedResponse(other)),
+        }
+    })
+}
+
+/// Switches the keyboard to another scancode set (1, 2 or 3)
+pub fn set_keyboard_scancode_set(set : u8) -> Result<(), Ps2Error> {
+    without_interrupts(|| Controller::new().keyboard_command_with_data(KBD_SCANCODE_SET, set))
+}
+
+/// Reads a byte for the keyboard interrupt, ignoring spurious interrupts,
+/// bytes from the second port and command responses
+pub(crate) fn read_keyboard_byte() -> Option<u8> {
+    let mut controller = Controller::new();
+    let status = controller.status();
+    if status & STATUS_OUTPUT_FULL == 0 || status & STATUS_AUX_DATA != 0 {
+        return None;
+    }
+    match unsafe { controller.data.read() } {
+        RESPONSE_ACK | RESPONSE_RESEND => None,
+        byte => Some(byte),
+    }
+}
+
+/// Reads a byte for the mouse interrupt, ignoring spurious interrupts & keyboard bytes
+pub(crate) fn read_aux_byte() -> Option<u8> {
+    let mut controller = Controller::new();
+    let status = controller.status();
+    if status & STATUS_OUTPUT_FULL == 0 || status & STATUS_AUX_DATA == 0 {
+        return None;
+    }
+    Some(unsafe { controller.data.read() })
+}
+
+/// Resets the keyboard, which also runs its self test
+pub fn reset_keyboard() -> Result<(), Ps2Error> {
+    without_interrupts(|| reset_keyboard_with(&mut Controller::new()))
+}
+
+fn reset_keyboard_with(controller : &mut Controller) -> Result<(), Ps2Error> {
+    controller.keyboard_command(KBD_RESET)?;
+    match controller.read_data_timeout(RESET_TIMEOUT)? {
+        RESPONSE_RESET_OK => {}
+        other => return Err(Ps2Error::UnexpectedResponse(other)),
+    }
+    controller.keyboard_command(KBD_ENABLE)
+}
+
+/// Turns the keyboard's Scroll, Num & Caps Lock LEDs on or off
+pub fn set_leds(scroll_lock : bool, num_lock : bool, caps_lock : bool) -> Result<(), Ps2Error> {
+    let leds = (scroll_lock as u8) | (num_lock as u8) << 1 | (caps_lock as u8) << 2;
+    without_interrupts(|| Controller::new().keyboard_command_with_data(KBD_SET_LEDS, leds))
+}
+
+/// Sets how fast held keys repeat.
+///
+/// `rate` ranges from 0 (30 repeats per second) to 31 (2 per second),
+/// `delay` from 0 (250ms before the first repeat) to 3 (1 second).
+pub fn set_typematic(rate : u8, delay : u8) -> Result<(), Ps2Error> {
+    let value = (delay & 0x03) << 5 | (rate & 0x1F);
+    without_interrupts(|| Controller::new().keyboard_command_with_data(KBD_TYPEMATIC, value))
+}
+
+/// Resets the machine through the controller's reset line, halting if that doesn't work
+pub fn reboot() -> ! {
+    x86_64::instructions::interrupts::disable();
+    let mut controller = Controller::new();
+    let _ = controller.send_command(CMD_RESET_CPU);
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
@@ -25,4 +25,85 @@ pub struct Vga {
 pub(crate) struct CrtController {
     horizontal_total_reg    : RegisterRW,
     end_horizontal_disp_reg : RegisterRW, 
-} 
\ No newline at end of file
+} 
+
+// Text Mode Cursor ======================================================================
+
+const CRTC_INDEX : u16 = 0x3D4;
+const CRTC_DATA  : u16 = 0x3D5;
+
+const CURSOR_START    : u8 = 0x0A;
+const CURSOR_END      : u8 = 0x0B;
+const CURSOR_LOC_HIGH : u8 = 0x0E;
+const CURSOR_LOC_LOW  : u8 = 0x0F;
+
+/// Bit of the cursor start register that hides the cursor
+const CURSOR_DISABLE  : u8 = 1 << 5;
+/// Scanlines in a character cell of the 80x25 text mode
+const CELL_HEIGHT     : u8 = 16;
+const TEXT_WIDTH      : usize = 80;
+
+/// The scanlines covered by the blinking cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Underline,
+    Block,
+}
+
+fn read_crtc(index : u8) -> u8 {
+    let mut index_port : RegisterRW = Port::new(CRTC_INDEX);
+    let mut data_port  : RegisterRW = Port::new(CRTC_DATA);
+    unsafe {
+        index_port.write(index);
+        data_port.read()
+    }
+}
+
+fn write_crtc(index : u8, data : u8) {
+    let mut index_port : RegisterRW = Port::new(CRTC_INDEX);
+    let mut data_port  : RegisterRW = Port::new(CRTC_DATA);
+    unsafe {
+        index_port.write(index);
+        data_port.write(data);
+    }
+}
+
+pub fn show_cursor() {
+    write_crtc(CURSOR_START, read_crtc(CURSOR_START) & !CURSOR_DISABLE);
+}
+
+pub fn hide_cursor() {
+    write_crtc(CURSOR_START, read_crtc(CURSOR_START) | CURSOR_DISABLE);
+}
+
+pub fn cursor_visible() -> bool {
+    read_crtc(CURSOR_START) & CURSOR_DISABLE == 0
+}
+
+/// Moves the cursor to a cell of the 80 column text screen
+pub fn set_cursor_position(col : usize, row : usize) {
+    let offset = (row * TEXT_WIDTH + col) as u16;
+    write_crtc(CURSOR_LOC_HIGH, (offset >> 8) as u8);
+    write_crtc(CURSOR_LOC_LOW, offset as u8);
+}
+
+/// The cell the cursor is on, as column & row
+pub fn cursor_position() -> (usize, usize) {
+    let offset = (read_crtc(CURSOR_LOC_HIGH) as usize) << 8 | read_crtc(CURSOR_LOC_LOW) as usize;
+    (offset % TEXT_WIDTH, offset / TEXT_WIDTH)
+}
+
+/// Sets the first & last scanlines of the cursor, keeping whether it's hidden
+pub fn set_cursor_scanlines(start : u8, end : u8) {
+    let start = start.min(CELL_HEIGHT - 1);
+    let end = end.min(CELL_HEIGHT - 1);
+    write_crtc(CURSOR_START, read_crtc(CURSOR_START) & 0xE0 | start);
+    write_crtc(CURSOR_END, read_crtc(CURSOR_END) & 0xE0 | end);
+}
+
+pub fn set_cursor_shape(shape : CursorShape) {
+    match shape {
+        CursorShape::Underline => set_cursor_scanlines(CELL_HEIGHT - 2, CELL_HEIGHT - 1),
+        CursorShape::Block     => set_cursor_scanlines(0, CELL_HEIGHT - 1),
+    }
+}
@@ -68,14 +68,14 @@ pub fn draw_string(x:usize,y:usize, text:&str, color:(vga::Color, vga::Color)) {
     let mut mut_y : usize = y;
     let mut mut_x : usize = x;
     let mut mut_color = color;
-    for chr in text.bytes() {
-        if chr == b'\n' { 
+    for chr in text.chars() {
+        if chr == '\n' { 
             mut_y += 1;
             mut_x = x; 
-        } else if is_color_escape(chr) {
-            mut_color = escape_code_to_color_tuple(chr, mut_color);
+        } else if chr.is_ascii() && is_color_escape(chr as u8) {
+            mut_color = escape_code_to_color_tuple(chr as u8, mut_color);
         } else {
-            draw(mut_x, mut_y, chr, mut_color.0, mut_color.1);
+            draw(mut_x, mut_y, crate::io::charset::to_cp437(chr), mut_color.0, mut_color.1);
             mut_x += 1;
         }
         
@@ -128,7 +128,7 @@ pub struct VgaWriter {
 impl Write for VgaWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         draw_string(self.x, self.y, s, self.color);
-        self.x += s.len();
+        self.x += s.chars().count();
         Ok(())
     }
 } 
@@ -97,21 +97,13 @@ impl Color {
     }
 }
 
+/// Shows what's been drawn to the back buffer (`GLOBAL_VGA_BUFFER_2`) by copying it to the screen.
+/// The terminal stops drawing over it until `vconsole::show` brings it back.
 pub fn swap_buffers() {
     without_interrupts(|| {
-        //crate::serial_println!("Swapping Buffers...");
-    //Preserve COLOR_VGA_TM Buffer in Low 32K of the GFX Buffer
-    {
-        GLOBAL_VGA_BUFFER.lock().copy_to(VGA_GFX_MODE_START);
-    }
-    //Copy Monochrome Buffer to Color Buffer
-    {
+        crate::io::vconsole::hide();
         GLOBAL_VGA_BUFFER_2.lock().copy_to(VGA_COLOR_TEXT_MODE_START);
-    }
-    //Copy Low 32K of the GFX Buffer to the Monochrome Buffer
-    {
-        GLOBAL_VGA_BUFFER_3.lock().copy_to(VGA_MONOCHROME_TEXT_MODE_START)
-    }
+        crate::devices::mouse::redraw_cursor();
     });
 }
 
@@ -152,6 +144,12 @@ impl ColorCode {
         self.0 = self.bg_as_u8() | fg
     } 
 
+    /// The same colours with foreground & background swapped. Bit 7 is the blink bit, so
+    /// a bright foreground becomes its dark counterpart as a background.
+    pub fn inverted(&self) -> ColorCode {
+        ColorCode((self.fg_as_u8() & 0x07) << 4 | self.bg_as_u8())
+    }
+
     pub fn set_bg_from_u8(&mut self, bg : u8) {
         self.0 = self.fg_as_u8() | bg
     } 
@@ -241,6 +239,19 @@ impl ScreenBuffer {
         self.data[y][x].read().code_point
     }
 
+    /// Swaps a cell's foreground & background colours, returning the cell as it was
+    pub fn invert_cell(&mut self, x:usize, y:usize) -> Char {
+        let original = self.data[y][x].read();
+        self.data[y][x].write(Char::new(original.code_point, original.color.inverted()));
+        original
+    }
+
+    pub fn write_row(&mut self, y:usize, row:&[Char; SCREEN_WIDTH]) {
+        for (x, c) in row.iter().enumerate() {
+            self.data[y][x].write(*c);
+        }
+    }
+
     pub fn copy_to(&self,addr : usize) {
         let buffer = ScreenBuffer::from_addr(addr);
         for y in 0..25 {
@@ -6,7 +6,6 @@ use x86_64::structures::idt::{
 
 use x86_64::structures::idt::PageFaultErrorCode;
 
-use x86_64::instructions::port::Port;
 use crate::{println};
 use crate::interrupts::pic::InterruptIndex;
 
@@ -21,6 +20,8 @@ lazy_static! {
 
         idt[InterruptIndex::TIMER.as_usize()].set_handler_fn(timer_tick);
         idt[InterruptIndex::KEYBOARD.as_usize()].set_handler_fn(keyboard_interrupt);
+        idt[InterruptIndex::MOUSE.as_usize()].set_handler_fn(mouse_interrupt);
+        idt[InterruptIndex::COM1.as_usize()].set_handler_fn(com1_interrupt);
 
         idt
     };
@@ -40,16 +41,36 @@ extern "x86-interrupt" fn breakpoint(_info : &mut InterruptStackFrame) {
 
 extern "x86-interrupt" fn timer_tick(_info : &mut InterruptStackFrame) {
     //print!(".");
+    super::record_irq(InterruptIndex::TIMER);
     super::global_timer::update();
+    crate::api::clock::tick();
+    crate::devices::keyboard::replay::tick();
+    crate::io::vconsole::tick();
     super::pic::fire_eoi(InterruptIndex::TIMER.as_u8());
 }
 
 extern "x86-interrupt" fn keyboard_interrupt(_info : &mut InterruptStackFrame) {
-    let mut port = Port::new(0x60);
-    crate::devices::keyboard::add_scancode(unsafe {port.read()});
+    super::record_irq(InterruptIndex::KEYBOARD);
+    if let Some(scancode) = crate::devices::ps2::read_keyboard_byte() {
+        crate::devices::keyboard::add_scancode(scancode);
+    }
     super::pic::fire_eoi(InterruptIndex::KEYBOARD.as_u8());
 }
 
+extern "x86-interrupt" fn mouse_interrupt(_info : &mut InterruptStackFrame) {
+    super::record_irq(InterruptIndex::MOUSE);
+    if let Some(byte) = crate::devices::ps2::read_aux_byte() {
+        crate::devices::mouse::add_byte(byte);
+    }
+    super::pic::fire_eoi(InterruptIndex::MOUSE.as_u8());
+}
+
+extern "x86-interrupt" fn com1_interrupt(_info : &mut InterruptStackFrame) {
+    super::record_irq(InterruptIndex::COM1);
+    crate::io::console::receive_serial();
+    super::pic::fire_eoi(InterruptIndex::COM1.as_u8());
+}
+
 extern "x86-interrupt" fn page_fault_handler(
     stack_frame: &mut InterruptStackFrame,
     error_code: PageFaultErrorCode,
@@ -4,6 +4,8 @@ pub mod pit;
 pub mod gdt;
 pub mod global_timer;
 
+use core::sync::atomic::{AtomicU64, Ordering};
+
 pub fn init() {
     idt::init();
     pic::init();
@@ -11,3 +13,20 @@ pub fn init() {
     x86_64::instructions::interrupts::enable();
 }
 
+/// Number of times each hardware IRQ line has fired since boot
+static IRQ_COUNTS : [AtomicU64; 16] = [
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+    AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0),
+];
+
+pub(crate) fn record_irq(index : pic::InterruptIndex) {
+    let irq = (index.as_u8() - pic::PIC_1_OFFSET) as usize;
+    IRQ_COUNTS[irq].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of times an IRQ line (0 - 15) has fired since boot
+pub fn irq_count(irq : u8) -> u64 {
+    IRQ_COUNTS.get(irq as usize).map_or(0, |count| count.load(Ordering::Relaxed))
+}
@@ -1,5 +1,7 @@
 use pic8259_simple::ChainedPics;
 use spin::Mutex;
+use x86_64::instructions::port::Port;
+use x86_64::instructions::interrupts::without_interrupts;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
@@ -20,7 +22,9 @@ pub fn init() {
 #[repr(u8)]
 pub(crate) enum InterruptIndex {
     TIMER = PIC_1_OFFSET,
-    KEYBOARD
+    KEYBOARD,
+    COM1 = PIC_1_OFFSET + 4,
+    MOUSE = PIC_2_OFFSET + 4,
 }
 
 impl InterruptIndex {
@@ -34,6 +38,50 @@ impl InterruptIndex {
 }
 
 
+const PIC_1_DATA : u16 = 0x21;
+const PIC_2_DATA : u16 = 0xA1;
+/// The IRQ the slave PIC is chained to
+const CASCADE_IRQ : u8 = 2;
+
+/// Unmasks an IRQ line (0 - 15), along with the cascade for lines on the slave PIC
+pub fn unmask(irq : u8) {
+    without_interrupts(|| {
+        if irq >= 8 {
+            clear_mask_bit(PIC_2_DATA, irq - 8);
+            clear_mask_bit(PIC_1_DATA, CASCADE_IRQ);
+        } else {
+            clear_mask_bit(PIC_1_DATA, irq);
+        }
+    });
+}
+
+/// Masks an IRQ line (0 - 15). The cascade is left alone, other lines on the slave PIC may need it.
+pub fn mask(irq : u8) {
+    without_interrupts(|| {
+        if irq >= 8 {
+            set_mask_bit(PIC_2_DATA, irq - 8);
+        } else {
+            set_mask_bit(PIC_1_DATA, irq);
+        }
+    });
+}
+
+fn clear_mask_bit(port : u16, bit : u8) {
+    let mut port : Port<u8> = Port::new(port);
+    unsafe {
+        let mask = port.read();
+        port.write(mask & !(1 << bit));
+    }
+}
+
+fn set_mask_bit(port : u16, bit : u8) {
+    let mut port : Port<u8> = Port::new(port);
+    unsafe {
+        let mask = port.read();
+        port.write(mask | (1 << bit));
+    }
+}
+
 pub fn fire_eoi(id : u8) {
     unsafe {
         PICS.lock().notify_end_of_interrupt(id);
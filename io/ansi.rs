@@ -0,0 +1,131 @@
+// A parser for the VT100/ANSI escape sequences understood by the terminal.
+// It only splits the byte stream into actions, the terminal decides what they do.
+// Reference: https://vt100.net/emu/dec_ansi_parser
+
+const ESC : u8 = 0x1B;
+const MAX_PARAMS : usize = 16;
+
+/// What a byte, or the sequence it completed, asks the terminal to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// A byte to display
+    Print(u8),
+    /// A control character such as '\n' or '\t'
+    Execute(u8),
+    /// An `ESC <final>` sequence, e.g. ESC 7 saves the cursor
+    Escape(u8),
+    /// A Control Sequence, `ESC [ <params> <final>`
+    Csi(Csi),
+}
+
+/// A Control Sequence's parameters & final byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Csi {
+    params  : [u16; MAX_PARAMS],
+    count   : usize,
+    /// Set when the parameters started with '?', for DEC private modes
+    pub private : bool,
+    pub final_byte : u8,
+}
+
+impl Csi {
+    /// The parameters, missing ones are 0
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count]
+    }
+
+    /// A parameter, or `default` if it's missing or 0
+    pub fn param(&self, index : usize, default : u16) -> u16 {
+        match self.params().get(index) {
+            Some(&value) if value != 0 => value,
+            _ => default
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    CsiParams,
+    /// Operating System Commands (e.g. window titles) are skipped
+    Osc,
+    OscEscape,
+}
+
+pub struct Parser {
+    state : State,
+    csi   : Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Parser {
+        Parser {
+            state : State::Ground,
+            csi   : Csi { params : [0; MAX_PARAMS], count : 0, private : false, final_byte : 0 },
+        }
+    }
+
+    /// Feeds a byte in, returning an action once there's something to do
+    pub fn advance(&mut self, byte : u8) -> Option<Action> {
+        match self.state {
+            State::Ground => match byte {
+                ESC => { self.state = State::Escape; None }
+                0x00..=0x1F | 0x7F => Some(Action::Execute(byte)),
+                _ => Some(Action::Print(byte)),
+            },
+            State::Escape => match byte {
+                b'[' => {
+                    self.csi = Csi { params : [0; MAX_PARAMS], count : 0, private : false, final_byte : 0 };
+                    self.state = State::CsiParams;
+                    None
+                }
+                b']' => { self.state = State::Osc; None }
+                ESC => None,
+                // Control characters still work in the middle of a sequence
+                0x00..=0x1F => Some(Action::Execute(byte)),
+                _ => { self.state = State::Ground; Some(Action::Escape(byte)) }
+            },
+            State::CsiParams => match byte {
+                b'0'..=b'9' => {
+                    if self.csi.count == 0 { self.csi.count = 1 }
+                    let param = &mut self.csi.params[self.csi.count - 1];
+                    *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    None
+                }
+                b';' => {
+                    if self.csi.count == 0 { self.csi.count = 1 }
+                    if self.csi.count < MAX_PARAMS { self.csi.count += 1 }
+                    None
+                }
+                b'?' => { self.csi.private = true; None }
+                0x40..=0x7E => {
+                    self.csi.final_byte = byte;
+                    self.state = State::Ground;
+                    Some(Action::Csi(self.csi))
+                }
+                ESC => { self.state = State::Escape; None }
+                0x00..=0x1F => Some(Action::Execute(byte)),
+                // Intermediate bytes aren't used by anything we support
+                _ => None
+            },
+            State::Osc => match byte {
+                0x07 => { self.state = State::Ground; None }
+                ESC => { self.state = State::OscEscape; None }
+                _ => None
+            },
+            State::OscEscape => {
+                self.state = if byte == b'\\' { State::Ground } else { State::Osc };
+                None
+            }
+        }
+    }
+
+    /// Forgets any partial sequence
+    pub fn reset(&mut self) {
+        self.state = State::Ground;
+    }
+}
+
+/// VGA colour numbers for the 8 ANSI colours (black, red, green, yellow, blue, magenta, cyan, white)
+pub const ANSI_TO_VGA : [u8; 8] = [0, 4, 2, 6, 1, 5, 3, 7];
@@ -0,0 +1,110 @@
+// UTF-8 decoding & the Unicode to code page 437 mapping used to draw text on the VGA text screen.
+// Reference: https://en.wikipedia.org/wiki/Code_page_437
+
+/// Drawn for characters with no CP437 glyph, a small square
+pub const REPLACEMENT_GLYPH : u8 = 0xFE;
+
+/// Characters of the glyphs 0x01 - 0x1F, drawn in place of control characters
+const CP437_LOW : [char; 32] = [
+    '\0', '☺', '☻', '♥', '♦', '♣', '♠', '•', '◘', '○', '◙', '♂', '♀', '♪', '♫', '☼',
+    '►', '◄', '↕', '‼', '¶', '§', '▬', '↨', '↑', '↓', '→', '←', '∟', '↔', '▲', '▼',
+];
+
+/// Characters of the glyphs 0x80 - 0xFF
+const CP437_HIGH : [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// The CP437 glyph for a character, `REPLACEMENT_GLYPH` if there isn't one.
+/// Accented Latin-1 letters without a glyph of their own fall back to the plain letter.
+pub fn to_cp437(chr : char) -> u8 {
+    if chr.is_ascii() {
+        return chr as u8;
+    }
+    if let Some(index) = CP437_HIGH.iter().position(|&c| c == chr) {
+        return 0x80 + index as u8;
+    }
+    if let Some(index) = CP437_LOW.iter().skip(1).position(|&c| c == chr) {
+        return 1 + index as u8;
+    }
+    let fallback = match chr {
+        '⌂' => return 0x7F,
+        'β' => return 0xE1,
+        'μ' => return 0xE6,
+        '∈' => return 0xEE,
+        'À' | 'Á' | 'Â' | 'Ã' => 'A',
+        'È' | 'Ê' | 'Ë' => 'E',
+        'Ì' | 'Í' | 'Î' | 'Ï' => 'I',
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ø' => 'O',
+        'Ù' | 'Ú' | 'Û' => 'U',
+        'Ý' => 'Y',
+        'Ð' => 'D',
+        'ã' => 'a',
+        'õ' | 'ø' => 'o',
+        'ý' => 'y',
+        '‘' | '’' | '´' => '\'',
+        '“' | '”' => '"',
+        '–' | '—' | '\u{AD}' => '-',
+        '×' => 'x',
+        '©' => 'c',
+        '®' => 'R',
+        _ => return REPLACEMENT_GLYPH
+    };
+    fallback as u8
+}
+
+/// Decodes a UTF-8 byte stream one byte at a time, for output that arrives a byte at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utf8Decoder {
+    code      : u32,
+    /// Continuation bytes still expected
+    remaining : u8,
+    /// Smallest code point the current sequence may encode, to reject overlong encodings
+    min       : u32,
+}
+
+impl Utf8Decoder {
+    pub const fn new() -> Utf8Decoder {
+        Utf8Decoder { code : 0, remaining : 0, min : 0 }
+    }
+
+    /// Feeds a byte in, passing on the characters it completes.
+    /// Invalid or cut short sequences come out as U+FFFD.
+    pub fn decode(&mut self, byte : u8, emit : &mut dyn FnMut(char)) {
+        if self.remaining > 0 {
+            if byte & 0xC0 == 0x80 {
+                self.code = self.code << 6 | (byte & 0x3F) as u32;
+                self.remaining -= 1;
+                if self.remaining == 0 {
+                    let chr = if self.code < self.min { None } else { core::char::from_u32(self.code) };
+                    emit(chr.unwrap_or(core::char::REPLACEMENT_CHARACTER));
+                }
+                return;
+            }
+            // The sequence was cut short, the byte starts something new
+            self.remaining = 0;
+            emit(core::char::REPLACEMENT_CHARACTER);
+        }
+
+        match byte {
+            0x00..=0x7F => emit(byte as char),
+            0xC2..=0xDF => self.start(byte & 0x1F, 1, 0x80),
+            0xE0..=0xEF => self.start(byte & 0x0F, 2, 0x800),
+            0xF0..=0xF4 => self.start(byte & 0x07, 3, 0x10000),
+            _ => emit(core::char::REPLACEMENT_CHARACTER),
+        }
+    }
+
+    fn start(&mut self, bits : u8, remaining : u8, min : u32) {
+        self.code = bits as u32;
+        self.remaining = remaining;
+        self.min = min;
+    }
+}
@@ -0,0 +1,284 @@
+// The console behind `print!`. Output is copied to every attached printer (the VGA terminal,
+// the serial port, a log buffer...) so what's shown on screen also ends up in the CI logs.
+// Until something is attached, output goes straight to the VGA terminal.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use pc_keyboard::{DecodedKey, KeyCode as RawKeyCode};
+use spin::Mutex;
+use x86_64::instructions::interrupts::{self, without_interrupts};
+
+use crate::devices::keyboard::{self, Modifiers};
+use crate::io::charset::Utf8Decoder;
+use crate::io::printer::Printer;
+use crate::io::serial::SerialPrinter;
+use crate::io::terminal::{VgaPrinter, WRITER};
+use crate::io::vconsole;
+
+/// Identifies an attached printer so it can be detached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputId(usize);
+
+static OUTPUTS : Mutex<Vec<(OutputId, Box<dyn Printer + Send>)>> = Mutex::new(Vec::new());
+static NEXT_ID : AtomicUsize = AtomicUsize::new(0);
+
+/// Whether bytes received on COM1 are typed as keys
+static SERIAL_INPUT : AtomicBool = AtomicBool::new(false);
+
+/// Attaches the VGA terminal & COM1, needs the heap
+pub fn init() -> (OutputId, OutputId) {
+    (attach(Box::new(VgaPrinter::new())), attach(Box::new(SerialPrinter::new())))
+}
+
+/// Copies everything printed from now on to `printer`
+pub fn attach(printer : Box<dyn Printer + Send>) -> OutputId {
+    let id = OutputId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    without_interrupts(|| OUTPUTS.lock().push((id, printer)));
+    id
+}
+
+/// Stops printing to an output, handing it back
+pub fn detach(id : OutputId) -> Option<Box<dyn Printer + Send>> {
+    without_interrupts(|| {
+        let mut outputs = OUTPUTS.lock();
+        let index = outputs.iter().position(|(output, _)| *output == id)?;
+        Some(outputs.remove(index).1)
+    })
+}
+
+/// Number of attached printers
+pub fn outputs() -> usize {
+    without_interrupts(|| OUTPUTS.lock().len())
+}
+
+#[doc(hidden)]
+pub fn _print(args : core::fmt::Arguments) {
+    let enabled = interrupts::are_enabled();
+    without_interrupts(|| {
+        let mut outputs = OUTPUTS.lock();
+        if outputs.is_empty() {
+            let _ = WRITER.lock().write_fmt(args);
+        } else {
+            for (_, output) in outputs.iter_mut() {
+                let _ = output.write_fmt(args);
+            }
+        }
+        drop(outputs);
+        vconsole::flush_unless_ticking(enabled);
+    });
+}
+
+/// Prints a single byte of UTF-8 to every output
+pub fn print_u8(b : u8) {
+    let enabled = interrupts::are_enabled();
+    without_interrupts(|| {
+        let mut outputs = OUTPUTS.lock();
+        if outputs.is_empty() {
+            WRITER.lock().print_u8(b);
+        } else {
+            for (_, output) in outputs.iter_mut() {
+                output.print_u8(b);
+            }
+        }
+        drop(outputs);
+        vconsole::flush_unless_ticking(enabled);
+    });
+}
+
+// Serial Input =========================================================================
+
+/// Types the characters received on COM1 as keys, so `StandardIn` & the line editor can be
+/// driven from the host. Characters skip the keyboard layout, the terminal's arrow, Home & End
+/// sequences become those keys. While this is on, `serial::try_receive` won't see what arrives.
+pub fn set_serial_input(enabled : bool) {
+    SERIAL_INPUT.store(enabled, Ordering::Relaxed);
+    if enabled {
+        // Initialising the port enables its receive interrupt
+        lazy_static::initialize(&crate::io::serial::SERIAL1);
+        crate::interrupts::pic::unmask(4);
+    } else {
+        crate::interrupts::pic::mask(4);
+    }
+}
+
+pub fn serial_input() -> bool {
+    SERIAL_INPUT.load(Ordering::Relaxed)
+}
+
+/// Called from the COM1 interrupt, drains the receive buffer
+pub(crate) fn receive_serial() {
+    // Only this interrupt takes the lock
+    let mut decoder = SERIAL_DECODER.lock();
+    while let Some(byte) = crate::io::serial::try_receive() {
+        if serial_input() {
+            decoder.decode(byte);
+        }
+    }
+}
+
+/// Where the serial decoder is in an escape sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EscapeState {
+    Ground,
+    /// After ESC
+    Escape,
+    /// After ESC [, with the first parameter so far
+    Csi(u8),
+    /// After ESC [ & a ';', the other parameters are ignored
+    CsiRest(u8),
+    /// After ESC O, which some terminals send for the arrow keys
+    Ss3,
+}
+
+/// Turns what a terminal sends into keys, without going through the keyboard layout.
+/// A lone Esc is only passed on once the next byte shows it doesn't start a sequence.
+struct SerialDecoder {
+    state : EscapeState,
+    utf8  : Utf8Decoder,
+}
+
+static SERIAL_DECODER : Mutex<SerialDecoder> = Mutex::new(SerialDecoder {
+    state : EscapeState::Ground,
+    utf8  : Utf8Decoder::new(),
+});
+
+impl SerialDecoder {
+    fn decode(&mut self, byte : u8) {
+        self.state = match (self.state, byte) {
+            (EscapeState::Ground, 0x1B) => EscapeState::Escape,
+            (EscapeState::Ground, _) => {
+                self.utf8.decode(byte, &mut type_char);
+                EscapeState::Ground
+            }
+            (EscapeState::Escape, b'[') => EscapeState::Csi(0),
+            (EscapeState::Escape, b'O') => EscapeState::Ss3,
+            (EscapeState::Escape, _) => {
+                type_char('\x1B');
+                self.state = EscapeState::Ground;
+                return self.decode(byte);
+            }
+            (EscapeState::Csi(param), b'0'..=b'9') => {
+                EscapeState::Csi(param.saturating_mul(10).saturating_add(byte - b'0'))
+            }
+            (EscapeState::Csi(param), b';') => EscapeState::CsiRest(param),
+            (EscapeState::Csi(param), 0x40..=0x7E) | (EscapeState::CsiRest(param), 0x40..=0x7E) => {
+                if let Some(key) = csi_key(param, byte) {
+                    keyboard::push_key(DecodedKey::RawKey(key), Modifiers::default());
+                }
+                EscapeState::Ground
+            }
+            (EscapeState::Csi(_), _) | (EscapeState::CsiRest(_), _) => self.state,
+            (EscapeState::Ss3, _) => {
+                if let Some(key) = csi_key(0, byte) {
+                    keyboard::push_key(DecodedKey::RawKey(key), Modifiers::default());
+                }
+                EscapeState::Ground
+            }
+        };
+    }
+}
+
+/// The key for the final byte of an escape sequence & its first parameter
+fn csi_key(param : u8, byte : u8) -> Option<RawKeyCode> {
+    Some(match (byte, param) {
+        (b'A', _) => RawKeyCode::ArrowUp,
+        (b'B', _) => RawKeyCode::ArrowDown,
+        (b'C', _) => RawKeyCode::ArrowRight,
+        (b'D', _) => RawKeyCode::ArrowLeft,
+        (b'H', _) | (b'~', 1) | (b'~', 7) => RawKeyCode::Home,
+        (b'F', _) | (b'~', 4) | (b'~', 8) => RawKeyCode::End,
+        (b'~', 2) => RawKeyCode::Insert,
+        (b'~', 3) => RawKeyCode::Delete,
+        (b'~', 5) => RawKeyCode::PageUp,
+        (b'~', 6) => RawKeyCode::PageDown,
+        _ => return None
+    })
+}
+
+/// Types a character received on COM1, control characters are typed with Ctrl held
+fn type_char(chr : char) {
+    let (chr, ctrl) = match chr {
+        // Terminals send CR for Enter & DEL for Backspace
+        '\r' | '\n'   => ('\n', false),
+        '\x7F' | '\x08' => ('\x08', false),
+        '\t' | '\x1B' => (chr, false),
+        '\0'..='\x1F' => (chr, true),
+        _             => (chr, false),
+    };
+    let modifiers = Modifiers { lctrl : ctrl, ..Modifiers::default() };
+    keyboard::push_key(DecodedKey::Unicode(chr), modifiers);
+}
+
+// Log Buffer ===========================================================================
+
+/// Keeps the last bytes printed in memory. Attach a clone & keep one to read them back.
+#[derive(Clone)]
+pub struct LogBuffer {
+    log : Arc<Mutex<Log>>,
+}
+
+struct Log {
+    bytes    : VecDeque<u8>,
+    capacity : usize,
+}
+
+impl LogBuffer {
+    /// A buffer holding up to `capacity` bytes, the oldest are dropped first
+    pub fn new(capacity : usize) -> LogBuffer {
+        LogBuffer {
+            log : Arc::new(Mutex::new(Log { bytes : VecDeque::with_capacity(capacity), capacity })),
+        }
+    }
+
+    /// What's been printed, bytes that aren't valid UTF-8 are replaced
+    pub fn contents(&self) -> String {
+        let bytes : Vec<u8> = without_interrupts(|| self.log.lock().bytes.iter().copied().collect());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub fn clear(&self) {
+        without_interrupts(|| self.log.lock().bytes.clear());
+    }
+}
+
+impl Printer for LogBuffer {
+    fn print_str(&mut self, s : &str) {
+        for byte in s.bytes() {
+            self.print_u8(byte);
+        }
+    }
+
+    fn print_u8(&mut self, b : u8) {
+        without_interrupts(|| {
+            let mut log = self.log.lock();
+            if log.capacity == 0 {
+                return;
+            }
+            if log.bytes.len() >= log.capacity {
+                log.bytes.pop_front();
+            }
+            log.bytes.push_back(b);
+        });
+    }
+
+    fn newline(&mut self) {
+        self.print_u8(b'\n');
+    }
+
+    fn tab(&mut self) {
+        self.print_u8(b'\t');
+    }
+}
+
+impl Write for LogBuffer {
+    fn write_str(&mut self, s : &str) -> core::fmt::Result {
+        self.print_str(s);
+        Ok(())
+    }
+}
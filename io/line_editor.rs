@@ -0,0 +1,315 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::devices::keyboard::{self, KeyCode, KeyEvent, KeyState};
+use crate::gfx::vga::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::io::printer::Printer;
+use crate::io::terminal::Terminal;
+use crate::io::vconsole;
+
+const DEFAULT_HISTORY_SIZE : usize = 32;
+
+/// Given the line up to the cursor, returns every possible completion of its last word
+pub type Completer = fn(&str) -> Vec<String>;
+
+/// Reads lines from the keyboard with editing keys & history, echoing them on the terminal.
+///
+/// Keys: Left/Right, Home/End or Ctrl+A/E to move, Backspace/Delete, Ctrl+K/U to delete
+/// to the end/start of the line, Ctrl+W to delete the previous word, Up/Down for history
+/// and Tab to complete.
+pub struct LineEditor {
+    history      : Vec<String>,
+    history_size : usize,
+    completer    : Option<Completer>,
+}
+
+impl LineEditor {
+    pub fn new() -> LineEditor {
+        LineEditor::with_history_size(DEFAULT_HISTORY_SIZE)
+    }
+
+    /// An editor remembering at most `history_size` lines
+    pub fn with_history_size(history_size : usize) -> LineEditor {
+        LineEditor {
+            history : Vec::new(),
+            history_size,
+            completer : None,
+        }
+    }
+
+    /// Sets the function called when Tab is pressed, `None` makes Tab do nothing
+    pub fn set_completer(&mut self, completer : Option<Completer>) {
+        self.completer = completer;
+    }
+
+    /// Previous lines, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// Adds a line to the history, skipping blank lines & repeats of the last line
+    pub fn add_history(&mut self, line : &str) {
+        if line.trim().is_empty() || self.history.last().map(|last| last == line).unwrap_or(false) {
+            return;
+        }
+        if self.history_size == 0 {
+            return;
+        }
+        if self.history.len() >= self.history_size {
+            self.history.remove(0);
+        }
+        self.history.push(String::from(line));
+    }
+
+    /// Prints the prompt & blocks until a line is entered, returning it without the newline
+    pub fn read_line(&mut self, prompt : &str) -> String {
+        self.read_line_on(0, prompt)
+    }
+
+    /// Reads a line on a virtual console, from the keys typed while it's active
+    pub fn read_line_on(&mut self, console : usize, prompt : &str) -> String {
+        let terminal = vconsole::terminal(console).expect("No Such Virtual Console");
+        crate::console_print!(console, "{}", prompt);
+        let start = without_interrupts(|| terminal.lock().position());
+        let mut edit = Edit { line : Vec::new(), cursor : 0, start, drawn : 0, console, terminal };
+
+        // Index into the history while browsing it, the line being typed is kept in `saved`
+        let mut browsing : Option<usize> = None;
+        let mut saved : Vec<char> = Vec::new();
+
+        loop {
+            let event = next_key_event(console);
+
+            match control_letter(&event) {
+                Some('a') => edit.cursor = 0,
+                Some('e') => edit.cursor = edit.line.len(),
+                Some('k') => edit.line.truncate(edit.cursor),
+                Some('u') => {
+                    edit.line.drain(..edit.cursor);
+                    edit.cursor = 0;
+                }
+                Some('w') => edit.delete_word(),
+                Some(_) => continue,
+                None => match event.code {
+                    KeyCode::CR | KeyCode::LF | KeyCode::KP_ENTER => break,
+                    KeyCode::BS => if edit.cursor > 0 {
+                        edit.cursor -= 1;
+                        edit.line.remove(edit.cursor);
+                    },
+                    KeyCode::DEL => if edit.cursor < edit.line.len() {
+                        edit.line.remove(edit.cursor);
+                    },
+                    KeyCode::ARROW_LEFT  => edit.cursor = edit.cursor.saturating_sub(1),
+                    KeyCode::ARROW_RIGHT => edit.cursor = (edit.cursor + 1).min(edit.line.len()),
+                    KeyCode::HOME => edit.cursor = 0,
+                    KeyCode::END  => edit.cursor = edit.line.len(),
+                    KeyCode::ARROW_UP => {
+                        let index = match browsing {
+                            Some(0) => continue,
+                            Some(index) => index - 1,
+                            None if self.history.is_empty() => continue,
+                            None => {
+                                saved = edit.line.clone();
+                                self.history.len() - 1
+                            }
+                        };
+                        browsing = Some(index);
+                        edit.replace(self.history[index].chars().collect());
+                    }
+                    KeyCode::ARROW_DOWN => match browsing {
+                        Some(index) if index + 1 < self.history.len() => {
+                            browsing = Some(index + 1);
+                            edit.replace(self.history[index + 1].chars().collect());
+                        }
+                        Some(_) => {
+                            browsing = None;
+                            edit.replace(core::mem::replace(&mut saved, Vec::new()));
+                        }
+                        None => continue,
+                    },
+                    KeyCode::HT => match self.completer {
+                        Some(completer) => edit.complete(completer),
+                        None => continue,
+                    },
+                    _ => match event.chr {
+                        Some(chr) if !chr.is_control() => {
+                            edit.line.insert(edit.cursor, chr);
+                            edit.cursor += 1;
+                        }
+                        _ => continue,
+                    },
+                },
+            }
+            edit.render();
+        }
+
+        edit.cursor = edit.line.len();
+        edit.render();
+        crate::console_print!(console, "\n");
+
+        let line : String = edit.line.iter().collect();
+        self.add_history(&line);
+        line
+    }
+}
+
+/// The line being edited & where it is on the screen
+struct Edit {
+    line   : Vec<char>,
+    cursor : usize,
+    /// Column & row of the line's first character
+    start  : (usize, usize),
+    /// Characters drawn by the last render, so leftovers can be blanked
+    drawn  : usize,
+    console  : usize,
+    terminal : &'static Mutex<Terminal>,
+}
+
+impl Edit {
+    fn replace(&mut self, line : Vec<char>) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    /// Deletes back to the start of the word before the cursor
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.line[start - 1] == ' ' { start -= 1 }
+        while start > 0 && self.line[start - 1] != ' ' { start -= 1 }
+        self.line.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Completes the word before the cursor as far as every candidate agrees,
+    /// listing the candidates if there's more than one
+    fn complete(&mut self, completer : Completer) {
+        let before : String = self.line[..self.cursor].iter().collect();
+        let word_start = self.line[..self.cursor].iter().rposition(|&c| c == ' ').map_or(0, |i| i + 1);
+        let word_len = self.cursor - word_start;
+        let candidates = completer(&before);
+
+        let first = match candidates.first() {
+            Some(first) => first,
+            None => return
+        };
+        let mut common : Vec<char> = first.chars().collect();
+        for candidate in &candidates[1..] {
+            let shared = common.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+            common.truncate(shared);
+        }
+
+        if common.len() > word_len {
+            self.line.splice(word_start..self.cursor, common.iter().copied());
+            self.cursor = word_start + common.len();
+        }
+        if candidates.len() == 1 && self.line.get(self.cursor) != Some(&' ') {
+            self.line.insert(self.cursor, ' ');
+            self.cursor += 1;
+        }
+
+        if candidates.len() > 1 {
+            // List them below the line, then start it again underneath
+            self.move_to_end();
+            crate::console_print!(self.console, "\n");
+            for candidate in &candidates {
+                crate::console_print!(self.console, "{}  ", candidate);
+            }
+            crate::console_print!(self.console, "\n");
+            let row = without_interrupts(|| self.terminal.lock().position().1);
+            self.start = (self.start.0.min(SCREEN_WIDTH - 1), row);
+            self.drawn = 0;
+        }
+    }
+
+    fn move_to_end(&mut self) {
+        self.cursor = self.line.len();
+        self.render();
+    }
+
+    /// Redraws the line & places the terminal's cursor
+    fn render(&mut self) {
+        let terminal = self.terminal;
+        without_interrupts(|| {
+            let mut term = terminal.lock();
+            term.set_position(self.start.0, self.start.1);
+
+            let len = self.line.len();
+            for i in 0..len.max(self.drawn) {
+                let chr = self.line.get(i).copied().unwrap_or(' ');
+                if term.position().0 >= SCREEN_WIDTH {
+                    self.wrap(&mut term);
+                }
+                term.print_char(chr);
+            }
+            self.drawn = len;
+
+            let offset = self.start.0 + self.cursor;
+            let row = self.start.1 + offset / SCREEN_WIDTH;
+            if row >= SCREEN_HEIGHT {
+                term.set_position(SCREEN_WIDTH, SCREEN_HEIGHT - 1);
+                self.wrap(&mut term);
+            } else {
+                term.set_position(offset % SCREEN_WIDTH, row);
+            }
+            term.flush();
+        });
+    }
+
+    /// Moves to the next row, scrolling the start of the line up with the screen
+    fn wrap(&mut self, term : &mut Terminal) {
+        if term.position().1 == SCREEN_HEIGHT - 1 {
+            self.start.1 = self.start.1.saturating_sub(1);
+        }
+        term.newline();
+    }
+}
+
+/// The letter of a Ctrl+letter combination, whether or not the decoder maps them to control characters
+fn control_letter(event : &KeyEvent) -> Option<char> {
+    if !event.modifiers.ctrl() {
+        return None;
+    }
+    match event.chr? {
+        chr @ '\u{1}'..='\u{1A}' => Some((chr as u8 - 1 + b'a') as char),
+        chr if chr.is_ascii_alphabetic() => Some(chr.to_ascii_lowercase()),
+        _ => None
+    }
+}
+
+/// Blocks until a key is pressed or repeated on a console
+fn next_key_event(console : usize) -> KeyEvent {
+    loop {
+        match keyboard::get_key_event_from(console) {
+            Some(event) if event.state != KeyState::Up => return event,
+            Some(_) => {}
+            None => x86_64::instructions::hlt(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref EDITOR : Mutex<LineEditor> = Mutex::new(LineEditor::new());
+}
+
+/// Reads a line using the shared editor & its history
+pub fn read_line(prompt : &str) -> String {
+    EDITOR.lock().read_line(prompt)
+}
+
+/// Reads a line on a virtual console using the shared editor & its history
+pub fn read_line_on(console : usize, prompt : &str) -> String {
+    EDITOR.lock().read_line_on(console, prompt)
+}
+
+/// Sets the completer used by `read_line`
+pub fn set_completer(completer : Option<Completer>) {
+    EDITOR.lock().set_completer(completer);
+}
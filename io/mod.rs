@@ -1,3 +1,8 @@
 pub mod serial;
 pub mod terminal;
-pub mod printer;
\ No newline at end of file
+pub mod printer;
+pub mod line_editor;
+pub mod ansi;
+pub mod vconsole;
+pub mod charset;
+pub mod console;
\ No newline at end of file
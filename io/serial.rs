@@ -12,10 +12,77 @@ lazy_static! {
     };
 }
 
+/// Line Status Register of COM1, bit 0 is set when a received byte is waiting
+const COM1_LINE_STATUS : u16 = 0x3F8 + 5;
+
+/// Returns the next byte received on COM1, or `None` if nothing has arrived
+pub fn try_receive() -> Option<u8> {
+    use x86_64::instructions::port::PortReadOnly;
+    use x86_64::instructions::interrupts::without_interrupts;
+    let mut status : PortReadOnly<u8> = PortReadOnly::new(COM1_LINE_STATUS);
+    if unsafe { status.read() } & 1 == 0 {
+        return None;
+    }
+    Some(without_interrupts(|| SERIAL1.lock().receive()))
+}
+
+/// Prints to COM1, for attaching to the console. Newlines are sent as CR LF.
+pub struct SerialPrinter {_private : ()}
+
+impl SerialPrinter {
+    pub fn new() -> SerialPrinter {
+        SerialPrinter {_private : ()}
+    }
+}
+
+impl crate::io::printer::Printer for SerialPrinter {
+    fn print_str(&mut self, s : &str) {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut port = SERIAL1.lock();
+            for byte in s.bytes() {
+                if byte == b'\n' {
+                    port.send(b'\r');
+                }
+                port.send(byte);
+            }
+        });
+    }
+
+    fn print_u8(&mut self, b : u8) {
+        x86_64::instructions::interrupts::without_interrupts(|| {
+            let mut port = SERIAL1.lock();
+            if b == b'\n' {
+                port.send(b'\r');
+            }
+            port.send(b);
+        });
+    }
+
+    fn newline(&mut self) {
+        self.print_str("\n");
+    }
+
+    fn tab(&mut self) {
+        self.print_str("\t");
+    }
+}
+
+impl core::fmt::Write for SerialPrinter {
+    fn write_str(&mut self, s : &str) -> core::fmt::Result {
+        use crate::io::printer::Printer;
+        self.print_str(s);
+        Ok(())
+    }
+}
+
 #[doc(hidden)]
 pub fn _print(args: ::core::fmt::Arguments) {
     use core::fmt::Write;
-    SERIAL1.lock().write_fmt(args).expect("Printing to serial failed");
+    use x86_64::instructions::interrupts::without_interrupts;
+    // Interrupts are disabled so handlers (e.g. hotkeys) can print without deadlocking
+    without_interrupts(|| {
+        SERIAL1.lock().write_fmt(args).expect("Printing to serial failed");
+    });
 }
 
 /// Prints to the host through the serial interface.
@@ -1,8 +1,12 @@
 use core::fmt::Write;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
 use crate::io::printer::Printer;
+use crate::io::ansi::{self, Action, Csi, Parser};
+use crate::io::charset::{self, Utf8Decoder};
 use x86_64::instructions::interrupts::{self, without_interrupts};
 use crate::gfx::vga::{
-    ScreenBuffer, ColorCode, Char, SCREEN_HEIGHT, SCREEN_WIDTH, Color
+    ColorCode, Char, SCREEN_HEIGHT, SCREEN_WIDTH, Color, GLOBAL_VGA_BUFFER
 };
 
 use tinix_fs::api::{FileWriter, File, FileInteractor};
@@ -11,71 +15,542 @@ use lazy_static::lazy_static;
 use spin::Mutex;
 
 lazy_static! {
-    pub static ref WRITER: Mutex<Terminal> = Mutex::new(Terminal::new(ColorCode::from_colors(
-        Color::White, Color::Blue
-    )));
+    /// The terminal of the first virtual console, where `print!` writes
+    pub static ref WRITER: Mutex<Terminal> = {
+        let mut terminal = Terminal::new(default_color());
+        terminal.active = true;
+        Mutex::new(terminal)
+    };
+}
+
+pub(crate) fn default_color() -> ColorCode {
+    ColorCode::from_colors(Color::White, Color::Blue)
 }
 
 pub struct Terminal {
     row     : usize,
     col     : usize,
     color   : ColorCode,
-    buffer  : &'static mut ScreenBuffer,
+    /// What's on this terminal's screen, whether or not it's the one shown
+    cells   : [Row; SCREEN_HEIGHT],
+    /// One bit per row changed since it was last copied to the screen
+    dirty   : u32,
+    /// Set for the terminal of the active virtual console, the only one flushed to the screen
+    active  : bool,
+    cursor_visible : bool,
+    /// Where the hardware cursor was last put, `None` if it has to be put again
+    synced_cursor  : Option<(usize, usize)>,
+    parser  : Parser,
+    utf8    : Utf8Decoder,
+    attrs   : Attributes,
+    /// Colours restored by SGR 0
+    default_attrs : Attributes,
+    /// Cursor & attributes stored by ESC 7 / CSI s
+    saved   : (usize, usize, Attributes),
+    /// First & last rows scrolled by newlines, set with DECSTBM
+    scroll_top    : usize,
+    scroll_bottom : usize,
+    /// Columns between tab stops
+    tab_width     : usize,
+    /// Rows that scrolled off the top, oldest first
+    scrollback    : VecDeque<Row>,
+    scrollback_depth : usize,
+    /// Rows scrolled back from the bottom, 0 while showing the live screen
+    view_offset   : usize,
+    /// The rows shown while scrolled back, `cells` keeps the live screen meanwhile.
+    /// Allocated with the scrollback so scrolling from the keyboard interrupt doesn't allocate.
+    view          : Vec<Row>,
+}
+
+type Row = [Char; SCREEN_WIDTH];
+
+const ALL_ROWS : u32 = (1 << SCREEN_HEIGHT) - 1;
+
+pub const DEFAULT_TAB_WIDTH : usize = 8;
+
+/// Rows of scrollback kept by `init_modules`
+pub const DEFAULT_SCROLLBACK_DEPTH : usize = 1000;
+
+/// SGR state, the colour code is worked out from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Attributes {
+    fg      : u8,
+    bg      : u8,
+    bold    : bool,
+    reverse : bool,
+}
+
+impl Attributes {
+    fn from_color(color : ColorCode) -> Attributes {
+        Attributes { fg : color.fg_as_u8(), bg : color.bg_as_u8(), bold : false, reverse : false }
+    }
+
+    fn color(&self) -> ColorCode {
+        // Bold is shown as the bright version of the foreground colour
+        let fg = if self.bold { self.fg | 0x08 } else { self.fg };
+        let code = ColorCode::from_u8(self.bg << 4 | fg);
+        if self.reverse { code.inverted() } else { code }
+    }
 }
 
 impl Terminal {
     fn clearrow(&mut self, row: usize) {
-        for col in 0..SCREEN_WIDTH {
-            self.buffer.set_char(col, row, Char::blank(self.color));
+        self.clear_cells(row, 0, SCREEN_WIDTH);
+    }
+
+    fn clear_cells(&mut self, row : usize, from : usize, to : usize) {
+        for col in from..to.min(SCREEN_WIDTH) {
+            self.set_cell(col, row, Char::blank(self.color));
         }
     }
 
-    fn new(color : ColorCode) -> Terminal {
+    fn set_cell(&mut self, col : usize, row : usize, c : Char) {
+        self.cells[row][col] = c;
+        // While scrolled back the change is drawn once the view comes back to the live screen
+        if self.view_offset == 0 {
+            self.dirty |= 1 << row;
+        }
+    }
+
+    /// A terminal drawn only once its virtual console is activated
+    pub(crate) fn new(color : ColorCode) -> Terminal {
+        let attrs = Attributes::from_color(color);
         Terminal {
             row     : SCREEN_HEIGHT - 1,
             col     : 0,
-            buffer  : ScreenBuffer::mono_text_mode80x25(),
-            color   : color
+            color   : color,
+            cells   : [[Char::blank(color); SCREEN_WIDTH]; SCREEN_HEIGHT],
+            dirty   : ALL_ROWS,
+            active  : false,
+            cursor_visible : true,
+            synced_cursor  : None,
+            parser  : Parser::new(),
+            utf8    : Utf8Decoder::new(),
+            attrs,
+            default_attrs : attrs,
+            saved   : (0, 0, attrs),
+            scroll_top    : 0,
+            scroll_bottom : SCREEN_HEIGHT - 1,
+            tab_width     : DEFAULT_TAB_WIDTH,
+            scrollback    : VecDeque::new(),
+            scrollback_depth : 0,
+            view_offset   : 0,
+            view          : Vec::new(),
         }
     }
+
+    fn read_row(&self, row : usize) -> Row {
+        self.cells[row]
+    }
+
+    /// Shows or stops drawing this terminal, showing it redraws the screen & cursor
+    pub(crate) fn set_active(&mut self, active : bool) {
+        self.active = active;
+        if !active {
+            return;
+        }
+        self.dirty = ALL_ROWS;
+        self.synced_cursor = None;
+        if self.cursor_visible && self.view_offset == 0 {
+            crate::devices::vga::show_cursor();
+        } else {
+            crate::devices::vga::hide_cursor();
+        }
+        self.flush();
+    }
+
+    /// Copies the rows changed since the last flush to the screen & moves the hardware cursor,
+    /// if this terminal is shown. Returns false if the screen was busy, the rows are then
+    /// copied by a later flush.
+    pub fn flush(&mut self) -> bool {
+        if !self.active {
+            return true;
+        }
+        if self.dirty != 0 {
+            let mut screen = match GLOBAL_VGA_BUFFER.try_lock() {
+                Some(screen) => screen,
+                None => return false
+            };
+            for row in 0..SCREEN_HEIGHT {
+                if self.dirty & 1 << row != 0 {
+                    let cells = if self.view_offset == 0 { &self.cells[row] } else { &self.view[row] };
+                    screen.write_row(row, cells);
+                }
+            }
+            self.dirty = 0;
+            drop(screen);
+            crate::devices::mouse::redraw_cursor();
+        }
+        let cursor = (self.col.min(SCREEN_WIDTH - 1), self.row);
+        if self.cursor_visible && self.view_offset == 0 && self.synced_cursor != Some(cursor) {
+            self.sync_cursor();
+        }
+        true
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn set_cursor_visible(&mut self, visible : bool) {
+        self.cursor_visible = visible;
+        if !self.active {
+            return;
+        }
+        if visible {
+            crate::devices::vga::show_cursor();
+        } else {
+            crate::devices::vga::hide_cursor();
+        }
+    }
+
+    fn set_attrs(&mut self, attrs : Attributes) {
+        self.attrs = attrs;
+        self.color = attrs.color();
+    }
+
+    /// Moves the rows of the scroll region up by one, blanking the last
+    fn scroll_up(&mut self) {
+        if self.scroll_top == 0 && self.scrollback_depth > 0 {
+            let row = self.read_row(0);
+            if self.scrollback.len() >= self.scrollback_depth {
+                self.scrollback.pop_front();
+            } else if self.view_offset != 0 {
+                // Keeps the rows being viewed at the same offset from the new bottom
+                self.view_offset += 1;
+            }
+            self.scrollback.push_back(row);
+        }
+        for row in self.scroll_top + 1..=self.scroll_bottom {
+            for col in 0..SCREEN_WIDTH {
+                let character = self.cells[row][col];
+                self.set_cell(col, row - 1, character);
+            }
+        }
+        self.clearrow(self.scroll_bottom);
+    }
+
+    /// Draws a glyph at the cursor, wrapping onto the next row first if the last one is full
+    fn put(&mut self, b : u8) {
+        if self.col >= SCREEN_WIDTH { self.newline(); }
+        self.set_cell(self.col, self.row, Char::new(b, self.color));
+        self.col += 1;
+    }
+
+    fn execute(&mut self, b : u8) {
+        match b {
+            b'\n' => self.newline(),
+            b'\t' => self.tab(),
+            b'\r' => self.col = 0,
+            // Backspace only moves the cursor, the character is left to be overwritten
+            0x08 => self.col = self.col.min(SCREEN_WIDTH - 1).saturating_sub(1),
+            // Form feed clears the screen
+            0x0C => {
+                for row in 0..SCREEN_HEIGHT { self.clearrow(row) }
+                self.set_position(0, 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn escape(&mut self, b : u8) {
+        match b {
+            b'7' => self.saved = (self.col, self.row, self.attrs),
+            b'8' => {
+                let (col, row, attrs) = self.saved;
+                self.set_position(col, row);
+                self.set_attrs(attrs);
+            }
+            // Full reset
+            b'c' => {
+                self.set_attrs(self.default_attrs);
+                self.scroll_top = 0;
+                self.scroll_bottom = SCREEN_HEIGHT - 1;
+                for row in 0..SCREEN_HEIGHT { self.clearrow(row) }
+                self.set_position(0, 0);
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, csi : &Csi) {
+        if csi.private {
+            // DECTCEM, ESC [ ? 25 h / l shows & hides the cursor
+            match (csi.param(0, 0), csi.final_byte) {
+                (25, b'h') => self.set_cursor_visible(true),
+                (25, b'l') => self.set_cursor_visible(false),
+                _ => {}
+            }
+            return;
+        }
+        let n = csi.param(0, 1) as usize;
+        match csi.final_byte {
+            b'A' => self.row = self.row.saturating_sub(n),
+            b'B' => self.row = (self.row + n).min(SCREEN_HEIGHT - 1),
+            b'C' => self.col = (self.col + n).min(SCREEN_WIDTH - 1),
+            b'D' => self.col = self.col.min(SCREEN_WIDTH - 1).saturating_sub(n),
+            b'G' => self.col = (n - 1).min(SCREEN_WIDTH - 1),
+            b'd' => self.row = (n - 1).min(SCREEN_HEIGHT - 1),
+            b'H' | b'f' => {
+                let row = csi.param(0, 1) as usize - 1;
+                let col = csi.param(1, 1) as usize - 1;
+                self.set_position(col.min(SCREEN_WIDTH - 1), row);
+            }
+            b'J' => self.erase_display(csi.param(0, 0)),
+            b'K' => self.erase_line(csi.param(0, 0)),
+            b'm' => self.select_graphic_rendition(csi.params()),
+            b'r' => {
+                let top = csi.param(0, 1) as usize - 1;
+                let bottom = (csi.param(1, SCREEN_HEIGHT as u16) as usize - 1).min(SCREEN_HEIGHT - 1);
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.set_position(0, 0);
+                }
+            }
+            b's' => self.escape(b'7'),
+            b'u' => self.escape(b'8'),
+            _ => {}
+        }
+    }
+
+    fn erase_display(&mut self, mode : u16) {
+        match mode {
+            0 => {
+                self.erase_line(0);
+                for row in self.row + 1..SCREEN_HEIGHT { self.clearrow(row) }
+            }
+            1 => {
+                for row in 0..self.row { self.clearrow(row) }
+                self.erase_line(1);
+            }
+            2 | 3 => for row in 0..SCREEN_HEIGHT { self.clearrow(row) },
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode : u16) {
+        let row = self.row;
+        match mode {
+            0 => self.clear_cells(row, self.col, SCREEN_WIDTH),
+            1 => self.clear_cells(row, 0, self.col + 1),
+            2 => self.clearrow(row),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params : &[u16]) {
+        let mut attrs = self.attrs;
+        if params.is_empty() {
+            attrs = self.default_attrs;
+        }
+        for &param in params {
+            match param {
+                0 => attrs = self.default_attrs,
+                1 => attrs.bold = true,
+                22 => attrs.bold = false,
+                7 => attrs.reverse = true,
+                27 => attrs.reverse = false,
+                30..=37 => attrs.fg = ansi::ANSI_TO_VGA[(param - 30) as usize],
+                39 => attrs.fg = self.default_attrs.fg,
+                40..=47 => attrs.bg = ansi::ANSI_TO_VGA[(param - 40) as usize],
+                49 => attrs.bg = self.default_attrs.bg,
+                90..=97 => attrs.fg = ansi::ANSI_TO_VGA[(param - 90) as usize] | 0x08,
+                100..=107 => attrs.bg = ansi::ANSI_TO_VGA[(param - 100) as usize] | 0x08,
+                _ => {}
+            }
+        }
+        self.set_attrs(attrs);
+    }
+
+    /// The column & row the next character will be written to
+    pub fn position(&self) -> (usize, usize) {
+        (self.col, self.row)
+    }
+
+    /// Moves where the next character will be written, clamped to the screen
+    pub fn set_position(&mut self, col : usize, row : usize) {
+        self.col = col.min(SCREEN_WIDTH);
+        self.row = row.min(SCREEN_HEIGHT - 1);
+    }
+
+    /// Sets the columns between tab stops, 8 by default
+    pub fn set_tab_width(&mut self, width : usize) {
+        self.tab_width = width.max(1);
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Prints a character, ASCII goes through the escape sequence parser
+    /// and anything else is drawn as its CP437 glyph
+    pub fn print_char(&mut self, chr : char) {
+        if !chr.is_ascii() {
+            self.put(charset::to_cp437(chr));
+            return;
+        }
+        match self.parser.advance(chr as u8) {
+            Some(Action::Print(b))   => self.put(b),
+            Some(Action::Execute(b)) => self.execute(b),
+            Some(Action::Escape(b))  => self.escape(b),
+            Some(Action::Csi(csi))   => self.csi(&csi),
+            None => {}
+        }
+    }
+
+    /// Draws a CP437 glyph as is, e.g. 0x03 for a heart rather than a control character
+    pub fn print_glyph(&mut self, glyph : u8) {
+        self.put(glyph);
+    }
+
+    /// Moves the blinking hardware cursor to where the next character will go
+    pub fn sync_cursor(&mut self) {
+        if !self.active {
+            return;
+        }
+        let cursor = (self.col.min(SCREEN_WIDTH - 1), self.row);
+        crate::devices::vga::set_cursor_position(cursor.0, cursor.1);
+        self.synced_cursor = Some(cursor);
+    }
+
+    /// Blanks everything from the cursor to the end of its row
+    pub fn clear_to_end_of_line(&mut self) {
+        for col in self.col..SCREEN_WIDTH {
+            self.set_cell(col, self.row, Char::blank(self.color));
+        }
+    }
+}
+
+// Scrollback ===========================================================================
+
+impl Terminal {
+    /// Keeps up to `depth` rows that scrolled off the screen, 0 turns the scrollback off.
+    /// Needs the heap, as the rows are allocated up front.
+    pub fn set_scrollback_depth(&mut self, depth : usize) {
+        self.scroll_to_bottom();
+        while self.scrollback.len() > depth {
+            self.scrollback.pop_front();
+        }
+        if depth == 0 {
+            self.scrollback = VecDeque::new();
+            self.view = Vec::new();
+        } else {
+            self.scrollback.reserve(depth - self.scrollback.len());
+            self.view.resize(SCREEN_HEIGHT, [Char::blank(self.color); SCREEN_WIDTH]);
+        }
+        self.scrollback_depth = depth;
+    }
+
+    pub fn scrollback_depth(&self) -> usize {
+        self.scrollback_depth
+    }
+
+    /// Rows currently held in the scrollback
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// Whether the screen shows the scrollback rather than the live output
+    pub fn is_scrolled_back(&self) -> bool {
+        self.view_offset != 0
+    }
+
+    /// Shows rows further back in the scrollback
+    pub fn scroll_back(&mut self, rows : usize) {
+        self.set_view_offset(self.view_offset.saturating_add(rows));
+    }
+
+    /// Shows rows closer to the live output
+    pub fn scroll_forward(&mut self, rows : usize) {
+        self.set_view_offset(self.view_offset.saturating_sub(rows));
+    }
+
+    /// Goes back to showing the live output
+    pub fn scroll_to_bottom(&mut self) {
+        if self.view_offset != 0 {
+            self.set_view_offset(0);
+        }
+    }
+
+    fn set_view_offset(&mut self, offset : usize) {
+        let offset = offset.min(self.scrollback.len());
+        if offset == self.view_offset {
+            return;
+        }
+        self.view_offset = offset;
+        self.dirty = ALL_ROWS;
+
+        let first = self.scrollback.len() - offset;
+        for row in 0..SCREEN_HEIGHT.min(offset) {
+            self.view[row] = self.scrollback[first + row];
+        }
+        for row in offset..SCREEN_HEIGHT {
+            self.view[row] = self.cells[row - offset];
+        }
+
+        if self.active {
+            // Redraws to hide or bring back the cursor
+            self.set_active(true);
+        }
+    }
+
+    /// Writes the scrollback followed by the live screen as text, one row per line
+    pub fn write_scrollback(&mut self, out : &mut dyn Write) -> core::fmt::Result {
+        for index in 0..self.scrollback.len() {
+            write_row_text(out, &self.scrollback[index])?;
+        }
+        for row in 0..SCREEN_HEIGHT {
+            write_row_text(out, &self.cells[row])?;
+        }
+        Ok(())
+    }
+}
+
+fn write_row_text(out : &mut dyn Write, cells : &Row) -> core::fmt::Result {
+    let mut line = [b' '; SCREEN_WIDTH];
+    for (byte, cell) in line.iter_mut().zip(cells.iter()) {
+        if cell.code_point.is_ascii_graphic() {
+            *byte = cell.code_point;
+        }
+    }
+    let end = line.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+    writeln!(out, "{}", core::str::from_utf8(&line[..end]).unwrap_or(""))
 }
 
 impl Printer for Terminal {
     fn print_str(&mut self, s:&str) {
+        // Drawn by the next flush, at most once a timer tick
         for byte in s.bytes() {
             self.print_u8(byte);
         }
-        crate::gfx::vga::swap_buffers();
     }
 
+    /// Takes UTF-8, a character is printed once all its bytes are in
     fn print_u8(&mut self, b:u8) {
-        if b == b'\n'               { self.newline(); return; }
-        if self.col >= SCREEN_WIDTH { self.newline(); return; }
-        if b == b'\t'               { self.tab();     return; }
-
-        self.buffer.set_char(self.col, self.row, Char::new(b, self.color));
-        self.col += 1;
-
+        let mut utf8 = self.utf8;
+        utf8.decode(b, &mut |chr| self.print_char(chr));
+        self.utf8 = utf8;
     }
 
+    /// Moves to the start of the next row, scrolling when it leaves the scroll region
     fn newline(&mut self) {
-        for row in 1..SCREEN_HEIGHT {
-            for col in 0..SCREEN_WIDTH {
-                let character = self.buffer.get_char(col, row);
-                self.buffer.set_char(col,row - 1,character);
-            }
-        }
-        self.clearrow(SCREEN_HEIGHT - 1);
         self.col = 0;
+        if self.row == self.scroll_bottom {
+            self.scroll_up();
+        } else if self.row < SCREEN_HEIGHT - 1 {
+            self.row += 1;
+        }
     }
 
+    /// Moves to the next tab stop, or the last column if there are no more on the row
     fn tab(&mut self) {
-        for _ in 0..=4 {
-            self.print_u8(b' ');
+        if self.col >= SCREEN_WIDTH {
+            return;
         }
+        let next = (self.col / self.tab_width + 1) * self.tab_width;
+        self.col = next.min(SCREEN_WIDTH - 1);
     }
-
-
 }
 
 impl Write for Terminal {
@@ -87,7 +562,7 @@ impl Write for Terminal {
 
 #[macro_export]
 macro_rules! print {
-    ($($arg:tt)*) => ($crate::io::terminal::_print(format_args!($($arg)*)));
+    ($($arg:tt)*) => ($crate::io::console::_print(format_args!($($arg)*)));
 }
 
 #[macro_export]
@@ -98,13 +573,65 @@ macro_rules! println {
 
 #[doc(hidden)]
 pub fn _print(args: core::fmt::Arguments) {
+    let enabled = interrupts::are_enabled();
     interrupts::without_interrupts(|| {
         WRITER.lock().write_fmt(args).unwrap();
+        crate::io::vconsole::flush_unless_ticking(enabled);
+    });
+}
+
+/// Prints to the first virtual console's terminal, for attaching to the console
+pub struct VgaPrinter {_private : ()}
+
+impl VgaPrinter {
+    pub fn new() -> VgaPrinter {
+        VgaPrinter {_private : ()}
+    }
+}
+
+impl Printer for VgaPrinter {
+    fn print_str(&mut self, s : &str) {
+        without_interrupts(|| WRITER.lock().print_str(s));
+    }
+
+    fn print_u8(&mut self, b : u8) {
+        without_interrupts(|| WRITER.lock().print_u8(b));
+    }
+
+    fn newline(&mut self) {
+        without_interrupts(|| WRITER.lock().newline());
+    }
+
+    fn tab(&mut self) {
+        without_interrupts(|| WRITER.lock().tab());
+    }
+}
+
+impl Write for VgaPrinter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.print_str(s);
+        Ok(())
+    }
+}
+
+/// Sets how many rows scrolled off the screen are kept, see `Terminal::set_scrollback_depth`
+pub fn set_scrollback_depth(depth : usize) {
+    without_interrupts(|| WRITER.lock().set_scrollback_depth(depth));
+}
+
+/// Writes the scrollback & the screen to the serial port
+pub fn dump_scrollback() {
+    without_interrupts(|| {
+        let mut term = WRITER.lock();
+        let mut serial = crate::io::serial::SERIAL1.lock();
+        let _ = writeln!(serial, "--- Scrollback ---");
+        let _ = term.write_scrollback(&mut *serial);
+        let _ = writeln!(serial, "------------------");
     });
 }
 
 pub fn get_char(x:usize, y:usize) -> Char {
-    WRITER.lock().buffer.get_char(x,y)
+    WRITER.lock().cells[y][x]
 }
 
 
@@ -149,12 +676,10 @@ impl FileWriter<u8> for StandardOut {
     }
 
     fn write(&mut self, data : u8) {
-        without_interrupts(|| {
-            WRITER.lock().print_u8(data);
-        })
+        crate::io::console::print_u8(data);
     }
 
     fn flush(&mut self) {
-        crate::gfx::swap();
+        crate::io::vconsole::flush();
     }
 }
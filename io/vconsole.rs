@@ -0,0 +1,177 @@
+// Virtual consoles, each with its own terminal, scrollback & keyboard input queue.
+// Only the active console is drawn to the screen and gets what's typed, Alt+F1..F6 switch.
+// Console 0 is the terminal behind `WRITER`, so `print!` & the keyboard functions use it.
+
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use conquer_once::spin::OnceCell;
+use spin::Mutex;
+use x86_64::instructions::interrupts::{self, without_interrupts};
+
+use crate::io::terminal::{self, Terminal, WRITER};
+
+pub const CONSOLE_COUNT : usize = 6;
+
+static ACTIVE : AtomicUsize = AtomicUsize::new(0);
+
+/// Set while the screen shows graphics swapped in by `gfx::swap` instead of the active console
+static HIDDEN : AtomicBool = AtomicBool::new(false);
+
+/// Consoles 1 and up
+static TERMINALS : OnceCell<Vec<Mutex<Terminal>>> = OnceCell::uninit();
+
+/// Scrollback rows kept by consoles 1 and up, it's reserved up front so it's kept small
+pub const SECONDARY_SCROLLBACK_DEPTH : usize = 100;
+
+/// Creates the other consoles' terminals, needs the heap. Their scrollback is at most
+/// `SECONDARY_SCROLLBACK_DEPTH` rows. Until this is called only console 0 exists.
+pub fn init() {
+    let depth = without_interrupts(|| WRITER.lock().scrollback_depth()).min(SECONDARY_SCROLLBACK_DEPTH);
+    let _ = TERMINALS.try_init_once(|| {
+        (1..CONSOLE_COUNT).map(|_| {
+            let mut terminal = Terminal::new(terminal::default_color());
+            terminal.set_scrollback_depth(depth);
+            Mutex::new(terminal)
+        }).collect()
+    });
+}
+
+/// The terminal of a console, `None` if there's no such console yet
+pub fn terminal(console : usize) -> Option<&'static Mutex<Terminal>> {
+    match console {
+        0 => Some(&*WRITER),
+        _ => TERMINALS.try_get().ok()?.get(console - 1),
+    }
+}
+
+/// The console shown on the screen
+pub fn active() -> usize {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+pub fn active_terminal() -> &'static Mutex<Terminal> {
+    terminal(active()).unwrap_or(&*WRITER)
+}
+
+/// Shows a console & sends it what's typed from now on. Safe to call from interrupt handlers,
+/// returns false if there's no such console or a terminal is in use.
+pub fn switch_to(console : usize) -> bool {
+    let next = match terminal(console) {
+        Some(next) => next,
+        None => return false
+    };
+    let current = active();
+    if console == current {
+        if !is_hidden() {
+            return true;
+        }
+        // Switching to the active console brings it back over graphics
+        return without_interrupts(|| match next.try_lock() {
+            Some(mut terminal) => {
+                HIDDEN.store(false, Ordering::Relaxed);
+                terminal.set_active(true);
+                true
+            }
+            None => false
+        });
+    }
+
+    let switched = without_interrupts(|| {
+        let mut old = match active_terminal().try_lock() {
+            Some(old) => old,
+            None => return false
+        };
+        let mut new = match next.try_lock() {
+            Some(new) => new,
+            None => return false
+        };
+        old.set_active(false);
+        ACTIVE.store(console, Ordering::Relaxed);
+        HIDDEN.store(false, Ordering::Relaxed);
+        new.set_active(true);
+        true
+    });
+    // The modifiers of the switching combo are released on the new console,
+    // the old one would otherwise keep them held down
+    if switched {
+        crate::devices::keyboard::release_modifiers(current);
+    }
+    switched
+}
+
+/// Stops drawing the active console so graphics can be shown in its place,
+/// what's printed meanwhile is kept & drawn by `show`
+pub(crate) fn hide() {
+    without_interrupts(|| {
+        HIDDEN.store(true, Ordering::Relaxed);
+        active_terminal().lock().set_active(false);
+        crate::devices::vga::hide_cursor();
+    });
+}
+
+/// Redraws the active console over graphics shown by `gfx::swap`
+pub fn show() {
+    without_interrupts(|| {
+        HIDDEN.store(false, Ordering::Relaxed);
+        active_terminal().lock().set_active(true);
+    });
+}
+
+/// Whether graphics are shown in place of the active console
+pub fn is_hidden() -> bool {
+    HIDDEN.load(Ordering::Relaxed)
+}
+
+/// Draws what's been printed to the active console
+pub fn flush() {
+    without_interrupts(|| {
+        active_terminal().lock().flush();
+    });
+}
+
+/// Flushes the active console, called from the timer interrupt so
+/// output is drawn at most once a tick however much is printed
+pub(crate) fn tick() {
+    if let Some(mut terminal) = active_terminal().try_lock() {
+        terminal.flush();
+    }
+}
+
+/// Flushes the active console after printing if interrupts were off, as no tick will.
+/// That covers output before interrupts are enabled & from panics & interrupt handlers.
+pub(crate) fn flush_unless_ticking(interrupts_enabled : bool) {
+    if !interrupts_enabled {
+        tick();
+    }
+}
+
+/// Brings the active console back to its live output if it's scrolled back
+pub fn scroll_to_bottom() {
+    without_interrupts(|| active_terminal().lock().scroll_to_bottom());
+}
+
+/// Writes to a console whether or not it's active, does nothing if there's no such console
+pub fn _print(console : usize, args : core::fmt::Arguments) {
+    if let Some(terminal) = terminal(console) {
+        let enabled = interrupts::are_enabled();
+        without_interrupts(|| {
+            terminal.lock().write_fmt(args).unwrap();
+            flush_unless_ticking(enabled);
+        });
+    }
+}
+
+/// Prints to a virtual console, `console_print!(1, "...")`
+#[macro_export]
+macro_rules! console_print {
+    ($console:expr, $($arg:tt)*) => ($crate::io::vconsole::_print($console, format_args!($($arg)*)));
+}
+
+/// Prints to a virtual console, appending a newline
+#[macro_export]
+macro_rules! console_println {
+    ($console:expr) => ($crate::console_print!($console, "\n"));
+    ($console:expr, $($arg:tt)*) => ($crate::console_print!($console, "{}\n", format_args!($($arg)*)));
+}
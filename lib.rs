@@ -29,6 +29,7 @@ pub mod utils;
 pub mod devices;
 pub mod maths;
 pub mod api;
+pub mod task;
 mod tests;
 
 pub use api as user; 
@@ -46,6 +47,19 @@ pub fn set_tick_rate(rate : usize) {
     unsafe {FREQ = rate};
 }
 
+/// Stores the tick rate in NVRAM, `load_tick_rate` restores it on the next boot
+pub fn save_tick_rate() -> Result<(), devices::nvram::NvramError> {
+    devices::nvram::set_u32(devices::nvram::KEY_TICK_RATE, get_frequency() as u32)
+}
+
+/// Sets the tick rate stored by `save_tick_rate`, returning false if none was stored
+pub fn load_tick_rate() -> bool {
+    match devices::nvram::get_u32(devices::nvram::KEY_TICK_RATE) {
+        Some(rate) if rate > 18 => { set_tick_rate(rate as usize); true }
+        _ => false
+    }
+}
+
 pub fn disable_interrupts() {
     x86_64::instructions::interrupts::disable();
 }
@@ -55,12 +69,27 @@ pub fn enable_interrupts() {
 }
 
 
-pub fn init_modules(_boot_info : &BootInfo) {
+pub fn init_modules(boot_info : &BootInfo) {
+    api::sysinf::record_memory_map(&boot_info.memory_map);
+    io::terminal::set_scrollback_depth(io::terminal::DEFAULT_SCROLLBACK_DEPTH);
+    io::vconsole::init();
+    io::console::init();
+    devices::keyboard::init();
+    devices::keyboard::hotkey::register_defaults();
+    devices::keyboard::load_layout();
+    let _ = devices::ps2::init();
+    devices::keyboard::detect_scancode_set();
+    let _ = devices::mouse::init();
+    devices::vga::set_cursor_shape(devices::vga::CursorShape::Underline);
+    devices::vga::show_cursor();
     interrupts::init();
+    let _ = api::clock::init();
 }
 
 pub fn init_modules_no_alloc() {
+    let _ = devices::ps2::init();
     interrupts::init();
+    let _ = api::clock::init();
 }
 
 pub fn breakpoint() {
@@ -70,6 +99,7 @@ pub fn breakpoint() {
 pub fn pause(ticks : usize) {
     for _ in 0..=ticks {
         x86_64::instructions::interrupts::enable_and_hlt();
+        api::clock::maintain();
     }
 }
 
@@ -0,0 +1,177 @@
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use crossbeam_queue::ArrayQueue;
+use futures_util::task::{waker, ArcWake};
+use x86_64::instructions::interrupts;
+
+use super::{Task, TaskId};
+
+/// Maximum number of tasks waiting to be polled, or waiting to be spawned
+const QUEUE_SIZE : usize = 128;
+
+/// Polls tasks when they're woken, halting the CPU when none are ready
+pub struct Executor {
+    tasks       : BTreeMap<TaskId, Task>,
+    task_queue  : Arc<ArrayQueue<TaskId>>,
+    spawn_queue : Arc<ArrayQueue<Task>>,
+    waker_cache : BTreeMap<TaskId, (Arc<TaskWaker>, Waker)>,
+    /// Set when a woken task didn't fit in the queue
+    overflowed  : Arc<AtomicBool>,
+}
+
+/// Spawns tasks onto an `Executor`, can be moved into the tasks themselves
+#[derive(Clone)]
+pub struct Spawner {
+    spawn_queue : Arc<ArrayQueue<Task>>,
+}
+
+impl Executor {
+    pub fn new() -> Executor {
+        Executor {
+            tasks       : BTreeMap::new(),
+            task_queue  : Arc::new(ArrayQueue::new(QUEUE_SIZE)),
+            spawn_queue : Arc::new(ArrayQueue::new(QUEUE_SIZE)),
+            waker_cache : BTreeMap::new(),
+            overflowed  : Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn spawner(&self) -> Spawner {
+        Spawner { spawn_queue : self.spawn_queue.clone() }
+    }
+
+    /// Adds a task & queues it to be polled, fails if too many tasks are ready to run
+    pub fn spawn(&mut self, task : Task) -> Result<(), Task> {
+        if self.task_queue.is_full() {
+            return Err(task);
+        }
+        self.insert(task);
+        Ok(())
+    }
+
+    /// Adds a task & wakes it, if the queue is full it's queued once there's room
+    fn insert(&mut self, task : Task) {
+        let task_id = task.id;
+        if self.tasks.insert(task_id, task).is_some() {
+            panic!("Task With Same ID Already Spawned");
+        }
+        let task_waker = Arc::new(TaskWaker {
+            task_id,
+            state      : AtomicU8::new(IDLE),
+            task_queue : self.task_queue.clone(),
+            overflowed : self.overflowed.clone(),
+        });
+        TaskWaker::wake_by_ref(&task_waker);
+        self.waker_cache.insert(task_id, (task_waker.clone(), waker(task_waker)));
+        super::LIVE_TASKS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of tasks that haven't completed yet
+    pub fn task_count(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Runs tasks forever, halting the CPU until the next interrupt whenever none are ready
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.spawn_new_tasks();
+            self.run_ready_tasks();
+            self.requeue_overflowed();
+            crate::api::clock::maintain();
+            self.sleep_if_idle();
+        }
+    }
+
+    fn spawn_new_tasks(&mut self) {
+        while let Ok(task) = self.spawn_queue.pop() {
+            self.insert(task);
+        }
+    }
+
+    fn run_ready_tasks(&mut self) {
+        let Self { tasks, task_queue, waker_cache, .. } = self;
+
+        while let Ok(task_id) = task_queue.pop() {
+            let (task, (task_waker, waker)) = match (tasks.get_mut(&task_id), waker_cache.get(&task_id)) {
+                (Some(task), Some(wakers)) => (task, wakers),
+                _ => continue, // Task No Longer Exists
+            };
+            // Wakes from now on queue the task again
+            task_waker.state.store(IDLE, Ordering::Release);
+            let mut context = Context::from_waker(waker);
+            match task.poll(&mut context) {
+                Poll::Ready(()) => {
+                    tasks.remove(&task_id);
+                    waker_cache.remove(&task_id);
+                    super::LIVE_TASKS.fetch_sub(1, Ordering::Relaxed);
+                }
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    /// Queues the tasks whose wakes didn't fit in the queue, now that it has been drained
+    fn requeue_overflowed(&mut self) {
+        if !self.overflowed.swap(false, Ordering::AcqRel) {
+            return;
+        }
+        for (task_waker, _) in self.waker_cache.values() {
+            if task_waker.state.load(Ordering::Acquire) != OVERFLOWED {
+                continue;
+            }
+            task_waker.state.store(QUEUED, Ordering::Release);
+            if self.task_queue.push(task_waker.task_id).is_err() {
+                task_waker.state.store(OVERFLOWED, Ordering::Release);
+                self.overflowed.store(true, Ordering::Release);
+                break;
+            }
+        }
+    }
+
+    fn sleep_if_idle(&self) {
+        interrupts::disable();
+        if self.task_queue.is_empty() && self.spawn_queue.is_empty() {
+            interrupts::enable_and_hlt();
+        } else {
+            interrupts::enable();
+        }
+    }
+}
+
+impl Spawner {
+    /// Queues a task to be spawned by the executor, fails if too many spawns are pending
+    pub fn spawn(&self, task : Task) -> Result<(), Task> {
+        self.spawn_queue.push(task).map_err(|e| e.0)
+    }
+}
+
+/// Not waiting to be polled
+const IDLE : u8 = 0;
+/// In the task queue
+const QUEUED : u8 = 1;
+/// Woken while the task queue was full, the executor queues it once there's room
+const OVERFLOWED : u8 = 2;
+
+struct TaskWaker {
+    task_id    : TaskId,
+    /// Keeps the task in the queue at most once
+    state      : AtomicU8,
+    task_queue : Arc<ArrayQueue<TaskId>>,
+    overflowed : Arc<AtomicBool>,
+}
+
+impl ArcWake for TaskWaker {
+    fn wake_by_ref(arc_self : &Arc<Self>) {
+        if arc_self.state.compare_exchange(IDLE, QUEUED, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return; // Already Queued
+        }
+        if arc_self.task_queue.push(arc_self.task_id).is_err() {
+            arc_self.state.store(OVERFLOWED, Ordering::Release);
+            arc_self.overflowed.store(true, Ordering::Release);
+            super::OVERFLOWED_WAKES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
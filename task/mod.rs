@@ -0,0 +1,77 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll};
+
+use alloc::boxed::Box;
+
+pub mod executor;
+
+pub use executor::{Executor, Spawner};
+
+static NEXT_ID : AtomicU64 = AtomicU64::new(0);
+
+/// Tasks spawned onto an executor that haven't completed yet
+static LIVE_TASKS : AtomicUsize = AtomicUsize::new(0);
+
+/// Wakes that found the task queue full, the tasks are queued again once there's room
+static OVERFLOWED_WAKES : AtomicUsize = AtomicUsize::new(0);
+
+/// Number of tasks, across every executor, that haven't completed yet
+pub fn live_tasks() -> usize {
+    LIVE_TASKS.load(Ordering::Relaxed)
+}
+
+/// Number of wakes since boot that had to wait for room in an executor's task queue
+pub fn overflowed_wakes() -> usize {
+    OVERFLOWED_WAKES.load(Ordering::Relaxed)
+}
+
+/// Number of tasks created since boot
+pub fn spawned_tasks() -> u64 {
+    NEXT_ID.load(Ordering::Relaxed)
+}
+
+/// A cooperative task, wrapping a future that runs until completion
+pub struct Task {
+    id     : TaskId,
+    future : Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl Task {
+    pub fn new(future : impl Future<Output = ()> + 'static) -> Task {
+        Task {
+            id     : TaskId::new(),
+            future : Box::pin(future),
+        }
+    }
+
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    fn poll(&mut self, context : &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TaskId(u64);
+
+impl TaskId {
+    fn new() -> TaskId {
+        TaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Runs the future on a new executor, along with anything it spawns. Never returns.
+pub fn run(future : impl Future<Output = ()> + 'static) -> ! {
+    let mut executor = Executor::new();
+    // A new executor's queue is empty, so this can't fail
+    let _ = executor.spawn(Task::new(future));
+    executor.run()
+}